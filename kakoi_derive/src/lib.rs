@@ -0,0 +1,122 @@
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Data, DeriveInput, Error, Fields, Ident};
+
+/// Derives `kakoi::node::typed::KakoiNode` for a struct with named fields.
+///
+/// Every field becomes a property of the node, except a field marked with
+/// `#[kakoi(id)]` which is used as the node id. Fields are converted with
+/// `IntoValue` and `FromValue`, so nested structs become `Value::Node` and
+/// `Vec`s become `Value::List`.
+#[proc_macro_derive(KakoiNode, attributes(kakoi))]
+pub fn derive_kakoi_node(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input.ident, "KakoiNode can only be derived for structs with named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "KakoiNode can only be derived for structs")),
+    };
+
+    let mut id: Option<&Ident> = None;
+    let mut properties = Vec::new();
+
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+
+        if is_id(field)? {
+            if id.is_some() {
+                return Err(Error::new_spanned(ident, "Only one field can be marked with #[kakoi(id)]"));
+            }
+            id = Some(ident);
+        } else {
+            properties.push(ident);
+        }
+    }
+
+    let property_names: Vec<String> = properties.iter().map(|ident| ident.to_string()).collect();
+
+    let into_id = match id {
+        Some(id) => quote!(::std::convert::Into::<::std::string::String>::into(self.#id)),
+        None => quote!(::std::string::String::new()),
+    };
+    let from_id = match id {
+        Some(id) => quote!(#id: ::std::convert::From::from(node.id.clone()),),
+        None => quote!(),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::kakoi::node::typed::KakoiNode for #name #ty_generics #where_clause {
+            fn into_node(self) -> ::kakoi::node::Node {
+                let mut properties = ::std::collections::HashMap::new();
+                #(
+                    properties.insert(
+                        #property_names.to_string(),
+                        ::kakoi::node::typed::IntoValue::into_value(self.#properties),
+                    );
+                )*
+
+                ::kakoi::node::Node {id: #into_id, properties: properties}
+            }
+
+            fn from_node(mut node: ::kakoi::node::Node) -> ::kakoi::entities::KakoiResult<Self> {
+                Ok(#name {
+                    #from_id
+                    #(
+                        #properties: ::kakoi::node::typed::take_property(&mut node, #property_names)?,
+                    )*
+                })
+            }
+        }
+
+        impl #impl_generics ::kakoi::node::typed::IntoValue for #name #ty_generics #where_clause {
+            fn into_value(self) -> ::kakoi::value::Value {
+                ::kakoi::value::Value::Node(::kakoi::node::typed::KakoiNode::into_node(self))
+            }
+        }
+
+        impl #impl_generics ::kakoi::node::typed::FromValue for #name #ty_generics #where_clause {
+            fn from_value(value: ::kakoi::value::Value) -> ::kakoi::entities::KakoiResult<Self> {
+                ::kakoi::node::typed::node_from_value(value)
+            }
+        }
+    })
+}
+
+fn is_id(field: &syn::Field) -> Result<bool, Error> {
+    let mut is_id = false;
+
+    for attr in &field.attrs {
+        if !attr.path().is_ident("kakoi") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") {
+                is_id = true;
+                Ok(())
+            } else {
+                Err(meta.error("Unknown kakoi attribute"))
+            }
+        })?;
+    }
+
+    Ok(is_id)
+}
@@ -0,0 +1,113 @@
+extern crate kakoi;
+#[macro_use]
+extern crate kakoi_derive;
+
+use kakoi::database::Database;
+use kakoi::datastore::memory::MemoryDataStore;
+use kakoi::entities::{Error, FilteredSelector, PathPart, Selector};
+use kakoi::node::typed::{FromValue, IntoValue, KakoiNode};
+use kakoi::value::Value;
+
+#[derive(Clone, Debug, PartialEq, KakoiNode)]
+struct Episode {
+    #[kakoi(id)]
+    id: String,
+    name: String,
+    rating: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, KakoiNode)]
+struct Serie {
+    #[kakoi(id)]
+    id: String,
+    name: String,
+    year: i64,
+    tags: Vec<String>,
+    episodes: Vec<Episode>,
+}
+
+#[derive(Debug, PartialEq, KakoiNode)]
+struct Title {
+    name: String,
+    year: i64,
+}
+
+#[derive(Debug, PartialEq, KakoiNode)]
+struct Library {
+    series: Vec<Title>,
+}
+
+fn sherlock() -> Serie {
+    Serie {
+        id: "sherlock".to_string(),
+        name: "Sherlock".to_string(),
+        year: 2010,
+        tags: vec!["crime".to_string(), "drama".to_string()],
+        episodes: vec![
+            Episode {id: "pink".to_string(), name: "A Study in Pink".to_string(), rating: Some(8.5)},
+            Episode {id: "banker".to_string(), name: "The Blind Banker".to_string(), rating: None},
+        ],
+    }
+}
+
+#[test]
+fn into_node() {
+    let node = sherlock().into_node();
+
+    assert_eq!(node.id, "sherlock");
+    assert_eq!(node.properties["year"], Value::I64(2010));
+    assert_eq!(node.properties["tags"], Value::List(vec![
+        Value::String("crime".into()),
+        Value::String("drama".into()),
+    ]));
+    assert!(!node.properties.contains_key("id"));
+
+    match node.properties["episodes"] {
+        Value::List(ref episodes) => match episodes[0] {
+            Value::Node(ref episode) => {
+                assert_eq!(episode.id, "pink");
+                assert_eq!(episode.properties["rating"], Value::F64(8.5));
+            },
+            ref value => panic!("{:?} where returned for episode, expected a Node", value),
+        },
+        ref value => panic!("{:?} where returned for episodes, expected a List", value),
+    }
+
+    assert_eq!(Serie::from_node(node).unwrap(), sherlock());
+}
+
+#[test]
+fn invalid_node() {
+    let mut node = sherlock().into_node();
+    node.properties.insert("year".to_string(), Value::String("2010".into()));
+
+    match Serie::from_node(node) {
+        Err(Error::InvalidValue(message)) => assert!(message.starts_with("year: "), "{}", message),
+        result => panic!("{:?} where returned, expected InvalidValue", result),
+    }
+}
+
+#[test]
+fn select_into() {
+    let mut store = MemoryDataStore::new();
+    let mut db = Database::new(&mut store);
+
+    db.set(&[PathPart::Field("series".into())], vec![sherlock()].into_value()).unwrap();
+
+    let library: Library = db.select_into(&Selector::traverse(
+        "series",
+        Selector::Multi(vec![Selector::Field("name".into()), Selector::Field("year".into())]),
+    )).unwrap();
+
+    assert_eq!(library, Library {series: vec![Title {name: "Sherlock".to_string(), year: 2010}]});
+
+    let episodes = db.select(&Selector::traverse("series", Selector::Multi(vec![
+        Selector::AllFields,
+        Selector::traverse("episodes", FilteredSelector::from(Selector::AllFields)),
+    ]))).unwrap().remove("series").unwrap();
+
+    match Vec::<Serie>::from_value(episodes) {
+        Err(Error::InvalidValue(message)) => assert!(message.starts_with("tags: "), "{}", message),
+        result => panic!("{:?} where returned, expected InvalidValue", result),
+    }
+}
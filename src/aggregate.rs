@@ -0,0 +1,175 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use value::Value;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The field of a group of an aggregate with the value grouped by.
+pub const GROUP_KEY: &'static str = "key";
+/// The field of a group of an aggregate with the aggregated value.
+pub const GROUP_VALUE: &'static str = "value";
+
+/// A statistic computed over the elements of a list. The fields can be paths,
+/// like `network.name`, or `$` for the elements of a list of values.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Aggregate {
+    /// The number of elements, as a `U64`.
+    Count,
+    /// The sum of the numbers, an `I64` unless there are floats or it overflows.
+    Sum(String),
+    /// The smallest of the values that aren't null, by `Value::total_cmp`.
+    Min(String),
+    /// The largest of the values that aren't null, by `Value::total_cmp`.
+    Max(String),
+    /// The mean of the numbers as a `F64`, or null without numbers.
+    Avg(String),
+    /// The values that aren't null, without duplicates, in the order of the list.
+    Distinct(String),
+}
+
+use self::Aggregate::*;
+
+impl Aggregate {
+    pub fn sum<S: Into<String>>(field: S) -> Aggregate {
+        Sum(field.into())
+    }
+
+    pub fn min<S: Into<String>>(field: S) -> Aggregate {
+        Min(field.into())
+    }
+
+    pub fn max<S: Into<String>>(field: S) -> Aggregate {
+        Max(field.into())
+    }
+
+    pub fn avg<S: Into<String>>(field: S) -> Aggregate {
+        Avg(field.into())
+    }
+
+    pub fn distinct<S: Into<String>>(field: S) -> Aggregate {
+        Distinct(field.into())
+    }
+
+    /// The field of the elements that is aggregated, if any.
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            &Count => None,
+            &Sum(ref field) | &Min(ref field) | &Max(ref field) | &Avg(ref field) | &Distinct(ref field) => Some(field),
+        }
+    }
+
+    /// Computes the aggregate from the values of its field, one per element.
+    pub fn apply(&self, values: Vec<Value>) -> Value {
+        match self {
+            &Count => Value::U64(values.len() as u64),
+            &Sum(_) => sum(&values),
+            &Min(_) => extreme(values, Ordering::Less),
+            &Max(_) => extreme(values, Ordering::Greater),
+            &Avg(_) => {
+                let numbers: Vec<f64> = values.iter().filter_map(as_f64).collect();
+                if numbers.is_empty() {
+                    Value::Null
+                } else {
+                    Value::F64(numbers.iter().sum::<f64>() / numbers.len() as f64)
+                }
+            },
+            &Distinct(_) => {
+                let mut distinct: Vec<Value> = Vec::new();
+                for value in values {
+                    if value != Value::Null && !distinct.iter().any(|d| d.total_cmp(&value) == Ordering::Equal) {
+                        distinct.push(value);
+                    }
+                }
+                Value::List(distinct)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            &Count => "count",
+            &Sum(_) => "sum",
+            &Min(_) => "min",
+            &Max(_) => "max",
+            &Avg(_) => "avg",
+            &Distinct(_) => "distinct",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        &Value::I64(n) => Some(n as f64),
+        &Value::U64(n) => Some(n as f64),
+        &Value::F64(n) => Some(n),
+        _ => None,
+    }
+}
+
+fn sum(values: &[Value]) -> Value {
+    let mut integer = Some(0i64);
+    let mut float = 0.0;
+
+    for value in values {
+        integer = match value {
+            &Value::I64(n) => integer.and_then(|sum| sum.checked_add(n)),
+            &Value::U64(n) => integer.and_then(|sum| i64::try_from(n).ok().and_then(|n| sum.checked_add(n))),
+            &Value::F64(_) => None,
+            _ => integer,
+        };
+        float += as_f64(value).unwrap_or(0.0);
+    }
+
+    integer.map_or(Value::F64(float), Value::I64)
+}
+
+fn extreme(values: Vec<Value>, ordering: Ordering) -> Value {
+    values
+        .into_iter()
+        .filter(|value| *value != Value::Null)
+        .fold(None, |extreme: Option<Value>, value| match extreme {
+            Some(ref extreme) if value.total_cmp(extreme) != ordering => Some(extreme.clone()),
+            _ => Some(value),
+        })
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use value::Value;
+
+    fn values() -> Vec<Value> {
+        vec![Value::I64(3), Value::Null, Value::U64(1), Value::I64(3), Value::String("x".into())]
+    }
+
+    #[test]
+    fn apply() {
+        assert_eq!(Count.apply(values()), Value::U64(5));
+        assert_eq!(Aggregate::sum("a").apply(values()), Value::I64(7));
+        assert_eq!(Aggregate::sum("a").apply(vec![Value::I64(1), Value::F64(0.5)]), Value::F64(1.5));
+        assert_eq!(Aggregate::sum("a").apply(vec![Value::I64(i64::max_value()), Value::I64(1)]), Value::F64(i64::max_value() as f64 + 1.0));
+        assert_eq!(Aggregate::min("a").apply(values()), Value::U64(1));
+        assert_eq!(Aggregate::max("a").apply(values()), Value::String("x".into()));
+        assert_eq!(Aggregate::avg("a").apply(values()), Value::F64(7.0 / 3.0));
+        assert_eq!(
+            Aggregate::distinct("a").apply(values()),
+            Value::List(vec![Value::I64(3), Value::U64(1), Value::String("x".into())])
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(Count.apply(vec![]), Value::U64(0));
+        assert_eq!(Aggregate::sum("a").apply(vec![]), Value::I64(0));
+        assert_eq!(Aggregate::min("a").apply(vec![Value::Null]), Value::Null);
+        assert_eq!(Aggregate::avg("a").apply(vec![]), Value::Null);
+        assert_eq!(Aggregate::distinct("a").apply(vec![]), Value::List(vec![]));
+    }
+}
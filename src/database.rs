@@ -1,33 +1,216 @@
-use std::collections::HashMap;
+use std::cmp::{self, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use aggregate::{Aggregate, GROUP_KEY, GROUP_VALUE};
 use datastore::DataStore;
+use encoding;
 use entities::*;
+use expression::Expression;
+use id::{IdGenerator, UuidV4Ids};
 use keys::*;
 use node::{Node, NodeProperties};
 use node::hashnode::{HashNode};
-use predicate::{MatchesPredicate, Predicate};
+use node::typed::FromValue;
+use parser;
+use predicate::{self, MatchesPredicate, Predicate};
 use value::{Value, ValueResolver};
 
 fn node_value(result: KakoiResult<Option<Node>>) -> KakoiResult<Value> {
     result.map(|n| n.map_or(Value::Null, Value::Node))
 }
 
+/// What a predicate needs loaded at the end of a field path.
+#[derive(Clone, Copy)]
+enum Hydrate<'b> {
+    Value,
+    Length,
+    Elements(&'b Predicate),
+}
+
+fn needs_hydration(predicate: &Predicate) -> bool {
+    match predicate {
+        &Predicate::All(ref predicates) | &Predicate::Any(ref predicates) => predicates.iter().any(needs_hydration),
+        &Predicate::Not(ref predicate) => needs_hydration(predicate),
+        &Predicate::AnyElement(..) | &Predicate::AllElements(..) |
+        &Predicate::NoElement(..) | &Predicate::Count(..) => true,
+        _ => predicate.get_fields().iter().any(|field| field.contains(predicate::SEPARATOR)),
+    }
+}
+
+/// Collects the fields a selector reads from a node itself, through nested
+/// `Multi`s, or sets them to `None` when it reads all fields.
+fn node_fields<'b>(selector: &'b Selector, fields: &mut Option<Vec<&'b str>>) -> KakoiResult {
+    match selector {
+        &Selector::AllFields => *fields = None,
+        &Selector::Field(ref field) | &Selector::Traverse(ref field, _) => if let Some(ref mut fields) = *fields {
+            fields.push(field);
+        },
+        &Selector::Alias(_, ref selector) => try!(node_fields(selector, fields)),
+        &Selector::Computed(_, ref expression) => if let Some(ref mut fields) = *fields {
+            fields.extend(expression.get_fields().into_iter().map(predicate::root_field));
+        },
+        &Selector::Multi(ref selectors) => for selector in selectors {
+            try!(node_fields(selector, fields));
+        },
+        &Selector::Aggregate(..) => return Err(not_a_list()),
+    }
+
+    Ok(())
+}
+
+fn not_a_list() -> Error {
+    Error::InvalidQuery("Only the elements of a list can be aggregated".to_string())
+}
+
+fn element_id(value: &Value) -> Option<&str> {
+    match value {
+        &Value::Link(ref id) => Some(id),
+        &Value::Node(ref node) => Some(&node.id),
+        _ => None,
+    }
+}
+
+/// Takes the page out of a list, and tells if there are more elements beyond it.
+fn paginate(mut list: Vec<Value>, page: &Page) -> KakoiResult<(Vec<Value>, bool)> {
+    let (start, end) = {
+        let position = |cursor: &str| list
+            .iter()
+            .position(|value| element_id(value) == Some(cursor))
+            .ok_or_else(|| Error::InvalidQuery(format!("Unknown cursor `{}`", cursor)));
+
+        let start = match page.after {
+            Some(ref cursor) => try!(position(cursor)) + 1,
+            None => 0,
+        };
+        let end = match page.before {
+            Some(ref cursor) => try!(position(cursor)),
+            None => list.len(),
+        };
+        (start, end)
+    };
+
+    let available = end.saturating_sub(start);
+    let skipped = cmp::min(page.offset, available);
+    let taken = page.limit.map_or(available - skipped, |limit| cmp::min(limit, available - skipped));
+
+    let (from, has_more) = if page.is_backwards() {
+        (end - skipped - taken, end - skipped - taken > start)
+    } else {
+        (start + skipped, start + skipped + taken < end)
+    };
+
+    list.truncate(from + taken);
+    Ok((list.split_off(from), has_more))
+}
+
+/// The start and end cursor of a page, the ids of its first and last element.
+fn cursors(list: &[Value]) -> (Value, Value) {
+    let cursor = |value: Option<&Value>| value
+        .and_then(element_id)
+        .map_or(Value::Null, |id| Value::String(id.to_string()));
+
+    (cursor(list.first()), cursor(list.last()))
+}
+
+fn page_value(list: Vec<Value>, has_more: bool, (start_cursor, end_cursor): (Value, Value)) -> Value {
+    let mut page = HashMap::new();
+    page.insert(PAGE_START_CURSOR.to_string(), start_cursor);
+    page.insert(PAGE_END_CURSOR.to_string(), end_cursor);
+    page.insert(PAGE_HAS_MORE.to_string(), Value::Boolean(has_more));
+    page.insert(PAGE_ELEMENTS.to_string(), Value::List(list));
+    Value::Map(page)
+}
+
+fn compare_keys(key: &OrderBy, a: &Value, b: &Value) -> Ordering {
+    match (a, b, key.nulls) {
+        (&Value::Null, &Value::Null, _) => Ordering::Equal,
+        (&Value::Null, _, Nulls::First) | (_, &Value::Null, Nulls::Last) => Ordering::Less,
+        (&Value::Null, _, Nulls::Last) | (_, &Value::Null, Nulls::First) => Ordering::Greater,
+        _ => match key.order {
+            Order::Ascending => a.total_cmp(b),
+            Order::Descending => b.total_cmp(a),
+        },
+    }
+}
+
+fn root_fields<'b>(fields: &[&'b str]) -> Vec<&'b str> {
+    let mut roots: Vec<&str> = Vec::new();
+
+    for field in fields {
+        let root = predicate::root_field(field);
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+
+    roots
+}
+
+enum StoredKey {
+    Hash(String),
+    List(String),
+}
+
+fn linked_keys(value: &Value, keys: &mut Vec<StoredKey>) {
+    match value {
+        &Value::Link(ref id) => keys.push(StoredKey::Hash(node_key(id))),
+        &Value::ListLink(ref id) => keys.push(StoredKey::List(list_key(id))),
+        &Value::Map(ref properties) => for value in properties.values() {
+            linked_keys(value, keys);
+        },
+        _ => {},
+    }
+}
+
+/// Decodes a stored value, returning it re-encoded if it was stored with an
+/// older encoding.
+fn migrate_value(value: &PrimitiveValue, keys: &mut Vec<StoredKey>) -> Option<PrimitiveValue> {
+    if let &PrimitiveValue::String(ref encoded) = value {
+        let decoded: Value = encoded.as_str().into();
+        linked_keys(&decoded, keys);
+
+        match decoded {
+            Value::Error(_) => None,
+            _ if encoding::is_current(encoded) => None,
+            _ => encoding::encode(&decoded).ok().map(PrimitiveValue::String),
+        }
+    } else {
+        None
+    }
+}
+
 pub struct Database<'a> {
     store: &'a mut DataStore,
+    ids: Box<IdGenerator>,
 }
 
 impl<'a> Database<'a> {
     pub fn new(store: &'a mut DataStore) -> Database<'a> {
-        Database {store: store}
+        Database {store: store, ids: Box::new(UuidV4Ids)}
+    }
+
+    /// Replaces the generator of list ids and of ids for nodes stored without one.
+    pub fn with_id_generator<T: IdGenerator + 'static>(mut self, ids: T) -> Database<'a> {
+        self.ids = Box::new(ids);
+        self
     }
 
     pub fn mutate(&mut self, mutation: Mutation) -> KakoiResult {
         match mutation.opertaion {
-            MutationOperation::Append(node) => self.append(mutation.path, node),
-            MutationOperation::Merge(properties) => self.merge(mutation.path, properties),
-            MutationOperation::Set(value) => self.set(mutation.path, value),
+            MutationOperation::Append(node) => self.append(&mutation.path, node),
+            MutationOperation::Merge(properties) => self.merge(&mutation.path, properties),
+            MutationOperation::Set(value) => self.set(&mutation.path, value),
         }
     }
 
+    /// Runs the operation on a path written like `series[name == "Sherlock"].year`,
+    /// see `parser::path`.
+    pub fn mutate_path(&mut self, path: &str, operation: MutationOperation) -> KakoiResult {
+        let path = try!(parser::parse_path(path));
+
+        self.mutate(Mutation {path: path, opertaion: operation})
+    }
+
     pub fn append(&mut self, path: Path, node: NodeType) -> KakoiResult {
         let keys = try!(self.resolve_path(path, true));
         println!("keys {:?}", keys);
@@ -37,6 +220,8 @@ impl<'a> Database<'a> {
             NodeType::Nodes(nodes) => nodes.into_iter().map(Value::Node).collect(),
             NodeType::Link(id) => vec![Value::Link(id)],
             NodeType::Links(ids) => ids.into_iter().map(Value::Link).collect(),
+            NodeType::Value(value) => vec![value],
+            NodeType::Values(values) => values,
         };
         println!("node_values {:?}", node_values);
 
@@ -47,6 +232,10 @@ impl<'a> Database<'a> {
         );
         println!("values {:?}", values);
 
+        if self.ids.content_addressed() {
+            return self.append_copy(path, values);
+        }
+
         for key in keys {
             try!(self.store.lpush(&key, &values)
                 .map_err(Error::Io));
@@ -55,11 +244,41 @@ impl<'a> Database<'a> {
         Ok(())
     }
 
+    /// Content addressed lists are shared, so instead of appending to one a copy is
+    /// stored under a new id and linked in its place.
+    fn append_copy(&mut self, path: Path, values: Vec<PrimitiveValue>) -> KakoiResult {
+        let (last, parent) = try!(path.split_last().ok_or(Error::EmptyPath));
+        let field = match last {
+            &PathPart::Field(ref field) | &PathPart::FieldFilter(ref field, _) => field,
+        };
+        let keys = if parent.is_empty() { vec![root_key()] } else { try!(self.resolve_path(parent, false)) };
+
+        for key in keys {
+            let list = match try!(self.get_field(&key, field)).into() {
+                Value::ListLink(id) => try!(self.store.lget(&list_key(&id)).map_err(Error::Io)).unwrap_or_default(),
+                Value::Null => Vec::new(),
+                _ => return Err(Error::Unknown),
+            };
+            let list = list.into_iter().chain(values.iter().cloned()).map(Value::from).collect();
+
+            let link = try!(self.resolve_value(&path, Value::List(list)));
+            try!(self.set_value(&key, last, &link));
+        }
+
+        Ok(())
+    }
+
     pub fn merge(&mut self, path: Path, properties: NodeProperties) -> KakoiResult {
         let keys = try!(self.resolve_path(path, false));
 
+        let mut values = HashMap::new();
+        for (property, value) in properties {
+            let value = try!(self.resolve_value(&path, value));
+            values.insert(property, value);
+        }
+
         for key in keys {
-            try!(self.store.hset_all(&key, &properties.clone().into_iter().map(|(k, v)| (k, v.into())).collect())
+            try!(self.store.hset_all(&key, &values)
                 .map_err(Error::Io));
         }
 
@@ -80,16 +299,34 @@ impl<'a> Database<'a> {
     }
 
     fn resolve_value(&mut self, path: &Path, value: Value) -> KakoiResult<PrimitiveValue> {
-        let mut resolver = ValueResolver::new();
-        let value: PrimitiveValue = resolver.resolve(value, path).into();
+        let store = &*self.store;
+        let stored = |link: &Value| match link {
+            &Value::Link(ref id) => store.hget_all(&node_key(id)).map(|node| node.is_some()).unwrap_or(false),
+            &Value::ListLink(ref id) => store.lget(&list_key(id)).map(|list| list.is_some()).unwrap_or(false),
+            _ => false,
+        };
+        let mut resolver = ValueResolver::new(&mut *self.ids, &stored);
+        let value = try!(PrimitiveValue::try_from(resolver.resolve(value, path)));
 
-        for list in resolver.lists {
-            let values = list.values.into_iter().map(|v| v.into()).collect();
-            try!(self.store.lpush(&list_key(&list.id), &values).map_err(Error::Io));
+        let lists: Vec<(String, Vec<PrimitiveValue>)> = try!(resolver.lists
+            .into_iter()
+            .map(|list| {
+                let values = try!(list.values.into_iter().map(PrimitiveValue::try_from).collect());
+                Ok((list_key(&list.id), values))
+            })
+            .collect());
+
+        let nodes: Vec<(String, HashMap<String, PrimitiveValue>)> = try!(resolver.nodes
+            .into_iter()
+            .map(|node| Ok((node_key(&node.id), try!(HashMap::try_from(node)))))
+            .collect());
+
+        for (key, values) in lists {
+            try!(self.store.lpush(&key, &values).map_err(Error::Io));
         }
 
-        for node in resolver.nodes {
-            try!(self.store.hset_all(&node_key(&node.id), &node.into()).map_err(Error::Io));
+        for (key, properties) in nodes {
+            try!(self.store.hset_all(&key, &properties).map_err(Error::Io));
         }
 
         Ok(value)
@@ -101,10 +338,10 @@ impl<'a> Database<'a> {
         let mut keys = vec![root_key()];
         let mut index = 0;
 
-        fn get_field<'b>(part: &'b PathPart) -> (&'b str, Option<&'b Predicate<'b>>) {
+        fn get_field<'b>(part: &'b PathPart) -> (&'b str, Option<&'b Predicate>) {
             match part {
-                &PathPart::Field(field) => (field, None),
-                &PathPart::FieldFilter(field, ref filter) => (field, Some(filter)),
+                &PathPart::Field(ref field) => (field, None),
+                &PathPart::FieldFilter(ref field, ref filter) => (field, Some(filter)),
             }
         }
 
@@ -130,7 +367,13 @@ impl<'a> Database<'a> {
                                 let nodes: Vec<String> =
                                     try!(self.get_filtered_list(&id, Some(&fields), &Some(filter)))
                                     .iter()
-                                    .map(|n| node_key(&n.id))
+                                    .filter_map(|v| {
+                                        if let &Value::Node(ref node) = v {
+                                            Some(node_key(&node.id))
+                                        } else {
+                                            None
+                                        }
+                                    })
                                     .collect();
 
                                 next_keys.extend(nodes);
@@ -170,20 +413,139 @@ impl<'a> Database<'a> {
         Ok(keys)
     }
 
+    /// Walks the graph from the root and rewrites every value that is stored with an
+    /// older encoding. Returns the number of rewritten hash fields and lists.
+    pub fn migrate(&mut self) -> KakoiResult<usize> {
+        let mut visited = HashSet::new();
+        let mut pending = vec![StoredKey::Hash(root_key())];
+        let mut migrated = 0;
+
+        while let Some(key) = pending.pop() {
+            match key {
+                StoredKey::Hash(key) => {
+                    if !visited.insert(key.clone()) { continue }
+
+                    let properties = try!(self.store.hget_all(&key).map_err(Error::Io))
+                        .unwrap_or_else(HashMap::new);
+
+                    for (property, value) in properties {
+                        if let Some(value) = migrate_value(&value, &mut pending) {
+                            try!(self.store.hset(&key, &property, &value).map_err(Error::Io));
+                            migrated += 1;
+                        }
+                    }
+                },
+                StoredKey::List(key) => {
+                    if !visited.insert(key.clone()) { continue }
+
+                    let values = try!(self.store.lget(&key).map_err(Error::Io))
+                        .unwrap_or_else(Vec::new);
+                    let mut changed = false;
+
+                    let values = values
+                        .into_iter()
+                        .map(|value| match migrate_value(&value, &mut pending) {
+                            Some(value) => {
+                                changed = true;
+                                value
+                            },
+                            None => value,
+                        })
+                        .collect();
+
+                    if changed {
+                        try!(self.store.del(&key).map_err(Error::Io));
+                        try!(self.store.lpush(&key, &values).map_err(Error::Io));
+                        migrated += 1;
+                    }
+                },
+            }
+        }
+
+        Ok(migrated)
+    }
+
+    /// Reads the whole graph reachable from the root. Every node is returned as a
+    /// `Value::Node` the first time it's reached and as a `Value::Link` after that,
+    /// so shared nodes and cycles are only read once.
+    pub fn export(&self) -> KakoiResult<NodeProperties> {
+        let mut seen = HashSet::new();
+        let root = try!(self.get_full_node(None));
+
+        root.map_or(Ok(HashMap::new()), |root| self.export_properties(root.properties, &mut seen))
+    }
+
+    /// Writes a graph read by `export` back, property by property, to the root.
+    pub fn import(&mut self, properties: NodeProperties) -> KakoiResult {
+        let mut properties: Vec<(String, Value)> = properties.into_iter().collect();
+        properties.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (property, value) in properties {
+            try!(self.set(&[PathPart::Field(property)], value));
+        }
+
+        Ok(())
+    }
+
+    fn export_properties(&self, properties: NodeProperties, seen: &mut HashSet<String>) -> KakoiResult<NodeProperties> {
+        // Properties are walked in order so that the same node is always the one
+        // that is expanded.
+        let mut properties: Vec<(String, Value)> = properties.into_iter().collect();
+        properties.sort_by(|a, b| a.0.cmp(&b.0));
+
+        properties
+            .into_iter()
+            .map(|(property, value)| self.export_value(value, seen).map(|value| (property, value)))
+            .collect()
+    }
+
+    fn export_value(&self, value: Value, seen: &mut HashSet<String>) -> KakoiResult<Value> {
+        match value {
+            Value::Link(id) => {
+                if !seen.insert(id.clone()) {
+                    return Ok(Value::Link(id));
+                }
+
+                match try!(self.get_full_node(Some(&id))) {
+                    Some(node) => {
+                        let properties = try!(self.export_properties(node.properties, seen));
+                        Ok(Value::Node(Node {id: id, properties: properties}))
+                    },
+                    None => Ok(Value::Link(id)),
+                }
+            },
+            Value::ListLink(id) => try!(self.get_list(&id))
+                .into_iter()
+                .map(|value| self.export_value(value, seen))
+                .collect::<KakoiResult<Vec<Value>>>()
+                .map(Value::List),
+            Value::Map(properties) => self.export_properties(properties, seen).map(Value::Map),
+            Value::Error(error) => Err(Error::InvalidValue(error)),
+            value => Ok(value),
+        }
+    }
+
     pub fn select(&self, selector: &Selector) -> KakoiResult<HashMap<String, Value>> {
         let root_node = try!(self.run_query(None, selector));
 
         match root_node {
             Value::Node(node) => Ok(node.properties),
-            _ => panic!("No root node returned"),
+            // Nothing has been stored yet.
+            _ => Ok(HashMap::new()),
         }
     }
 
+    /// Selects from the root node and reads the result into a typed value, such as
+    /// a struct deriving `KakoiNode`.
+    pub fn select_into<T: FromValue>(&self, selector: &Selector) -> KakoiResult<T> {
+        self.select(selector).and_then(|properties| T::from_value(Value::Map(properties)))
+    }
+
     fn run_query(&self, node_id: Option<&str>, selector: &Selector) -> KakoiResult<Value> {
         match selector {
             &Selector::AllFields => node_value(self.get_full_node(node_id)),
             &Selector::Field(ref field) => node_value(self.get_node(node_id, vec![field])),
-            &Selector::Traverse(ref field, selector) => {
+            &Selector::Traverse(ref field, ref selector) => {
                 let node = try!(self.get_node(node_id, vec![field]));
 
                 match node {
@@ -194,35 +556,18 @@ impl<'a> Database<'a> {
                     None => Ok(Value::Null),
                 }
             },
-            &Selector::Multi(ref selectors) => {
-                let mut all_fields = false;
-                let mut fields = Vec::new();
-                let mut traverse = HashMap::new();
-
-                for selector in selectors {
-                    match selector {
-                        &Selector::AllFields => all_fields = true,
-                        &Selector::Field(field) => fields.push(field),
-                        &Selector::Traverse(field, selector) => {
-                            fields.push(field);
-                            traverse.insert(field, selector);
-                        }
-                        &Selector::Multi(_) => return Err(Error::MultiInMulti),
-                    }
-                }
+            &Selector::Aggregate(..) => Err(not_a_list()),
+            &Selector::Multi(_) | &Selector::Alias(..) | &Selector::Computed(..) => {
+                let mut fields = Some(Vec::new());
+                try!(node_fields(selector, &mut fields));
 
-                let node = try!(
-                    if all_fields { self.get_full_node(node_id) }
-                    else          { self.get_node(node_id, fields) }
-                );
+                let node = try!(match fields {
+                    Some(fields) => self.get_node(node_id, fields),
+                    None => self.get_full_node(node_id),
+                });
 
                 match node {
-                    Some(mut node) => {
-                        for (field, selector) in traverse.iter() {
-                            node = try!(self.traverse_field(node, field, selector));
-                        }
-                        Ok(Value::Node(node))
-                    }
+                    Some(node) => self.select_fields(node, selector).map(Value::Node),
                     None => Ok(Value::Null),
                 }
             },
@@ -230,42 +575,156 @@ impl<'a> Database<'a> {
     }
 
     fn traverse_field(&self, mut node: Node, field: &str, selector: &FilteredSelector) -> KakoiResult<Node> {
-        let value = node.properties[field].clone();
+        let value = node.properties.get(field).map_or(Value::Null, Clone::clone);
         let sub_query = try!(self.traverse_value(&value, selector));
         node.properties.insert(field.to_owned(), sub_query);
         Ok(node)
     }
 
+    /// Selects from a node that is already read with the fields the selector reads,
+    /// keeping only the selected fields.
+    fn select_fields(&self, node: Node, selector: &Selector) -> KakoiResult<Node> {
+        let mut selected = HashMap::new();
+        try!(self.select_properties(&node, selector, &mut selected));
+        Ok(Node {id: node.id, properties: selected})
+    }
+
+    fn select_properties(&self, node: &Node, selector: &Selector, selected: &mut NodeProperties) -> KakoiResult {
+        let get = |field: &str| node.properties.get(field).map_or(Value::Null, Clone::clone);
+
+        match selector {
+            // Fields selected by the other selectors of a `Multi` take precedence.
+            &Selector::AllFields => for (field, value) in &node.properties {
+                selected.entry(field.clone()).or_insert_with(|| value.clone());
+            },
+            &Selector::Field(ref field) => {
+                selected.insert(field.clone(), get(field));
+            },
+            &Selector::Traverse(ref field, ref selector) => {
+                selected.insert(field.clone(), try!(self.traverse_value(&get(field), selector)));
+            },
+            &Selector::Multi(ref selectors) => for selector in selectors {
+                try!(self.select_properties(node, selector, selected));
+            },
+            &Selector::Alias(ref alias, ref selector) => {
+                let mut aliased = HashMap::new();
+                try!(self.select_properties(node, selector, &mut aliased));
+
+                if aliased.len() != 1 {
+                    return Err(Error::InvalidQuery(format!("The alias `{}` must select a single field", alias)));
+                }
+                selected.insert(alias.clone(), aliased.into_iter().next().unwrap().1);
+            },
+            &Selector::Computed(ref name, ref expression) => {
+                selected.insert(name.clone(), try!(self.compute(node, expression)));
+            },
+            &Selector::Aggregate(..) => return Err(not_a_list()),
+        }
+
+        Ok(())
+    }
+
+    fn compute(&self, node: &Node, expression: &Expression) -> KakoiResult<Value> {
+        let mut node = node.clone();
+        try!(self.hydrate_expression(&mut node, expression));
+        Ok(expression.evaluate(&Value::Node(node)))
+    }
+
+    /// Loads the linked nodes and lists the expression reads into the node.
+    fn hydrate_expression(&self, node: &mut Node, expression: &Expression) -> KakoiResult {
+        match expression {
+            &Expression::Field(ref field) if field.contains(predicate::SEPARATOR) =>
+                self.hydrate_path(node, field, Hydrate::Value),
+            &Expression::Length(ref field) => self.hydrate_path(node, field, Hydrate::Length),
+            &Expression::Concat(ref expressions) => {
+                for expression in expressions {
+                    try!(self.hydrate_expression(node, expression));
+                }
+                Ok(())
+            },
+            &Expression::Add(ref a, ref b) | &Expression::Sub(ref a, ref b) |
+            &Expression::Mul(ref a, ref b) | &Expression::Div(ref a, ref b) => {
+                try!(self.hydrate_expression(node, a));
+                self.hydrate_expression(node, b)
+            },
+            _ => Ok(()),
+        }
+    }
+
     fn traverse_value<'b>(&self, value: &Value, selector: &'b FilteredSelector) -> KakoiResult<Value> {
         match value {
-            &Value::Link(ref node_id) => self.run_query(Some(node_id), &selector.selector),
+            &Value::Link(ref node_id) => {
+                if let Some(ref filter) = selector.filter {
+                    let node = try!(self.get_node(Some(node_id), root_fields(&filter.get_fields())));
+                    if !try!(self.matches_filter(node, filter)) {
+                        return Ok(Value::Null);
+                    }
+                }
+                self.run_query(Some(node_id), &selector.selector)
+            },
+            &Value::Map(ref properties) => {
+                if let Some(ref filter) = selector.filter {
+                    let node = Node {id: String::new(), properties: properties.clone()};
+                    if !try!(self.matches_filter(Some(node), filter)) {
+                        return Ok(Value::Null);
+                    }
+                }
+                self.select_from_map(properties, &selector.selector)
+            },
+            &Value::Null => Ok(Value::Null),
             &Value::ListLink(ref id) => {
                 let fields = selector.get_fields();
                 let fields: Option<&[&str]> = match fields {
                     Some(ref fields) => Some(fields),
                     None => None,
                 };
-                let list = try!(self.get_filtered_list(id, fields, &selector.filter.as_ref()));
-
-                if let Selector::Traverse(field, selector) = selector.selector {
-                    let list: KakoiResult<Vec<Node>> = list
-                        .into_iter()
-                        .map(|mut node| {
-                            let traversed_value = try!(self.traverse_value(&node.properties[field], selector));
-                            node.properties.insert(field.to_string(), traversed_value);
-                            Ok(node)
-                        })
-                        .collect();
+                let (list, has_more, cursors) = match selector.page {
+                    Some(ref page) if selector.filter.is_none() && selector.order_by.is_empty() =>
+                        try!(self.get_list_page(id, fields, page)),
+                    ref page => {
+                        let mut list = try!(self.get_filtered_list(id, fields, &selector.filter.as_ref()));
+                        if !selector.order_by.is_empty() {
+                            list = try!(self.sort(list, &selector.order_by));
+                        }
+                        let (list, has_more) = match page {
+                            &Some(ref page) => try!(paginate(list, page)),
+                            &None => (list, false),
+                        };
+                        let cursors = cursors(&list);
+                        (list, has_more, cursors)
+                    },
+                };
 
-                    return list.map(Value::List);
+                if let Selector::Aggregate(ref aggregate, ref group_by) = selector.selector {
+                    return self.aggregate(list, aggregate, group_by);
                 }
 
-                Ok(Value::List(list))
+                let list = try!(list
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::Node(node) => self.select_fields(node, &selector.selector).map(Value::Node),
+                        Value::Map(properties) => self.select_from_map(&properties, &selector.selector),
+                        value => Ok(value),
+                    })
+                    .collect::<KakoiResult<Vec<Value>>>());
+
+                Ok(match selector.page {
+                    Some(_) => page_value(list, has_more, cursors),
+                    None => Value::List(list),
+                })
             }
             _ => Err(Error::FieldIsNotTraversable),
         }
     }
 
+    fn select_from_map(&self, properties: &NodeProperties, selector: &Selector) -> KakoiResult<Value> {
+        let node = Node {id: String::new(), properties: properties.clone()};
+        let mut selected = HashMap::new();
+        try!(self.select_properties(&node, selector, &mut selected));
+
+        Ok(Value::Map(selected))
+    }
+
     fn get_list(&self, id: &str) -> KakoiResult<Vec<Value>> {
         let list = self.store.lget(&list_key(id));
 
@@ -279,36 +738,249 @@ impl<'a> Database<'a> {
 
     }
 
-    fn get_filtered_list(&self, id: &str, fields: Option<&[&str]>, filter: &Option<&Predicate>) -> KakoiResult<Vec<Node>> {
-        let list = try!(
-            try!(self.get_list(id))
+    fn get_filtered_list(&self, id: &str, fields: Option<&[&str]>, filter: &Option<&Predicate>) -> KakoiResult<Vec<Value>> {
+        let fields = fields.map(root_fields);
+        let hydrate = filter.map_or(false, needs_hydration);
+
+        let mut list = Vec::new();
+
+        for value in try!(self.get_list(id)) {
+            let value = match try!(self.load_element(value, &fields)) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let matches = match (filter, &value) {
+                (&None, _) => true,
+                (&Some(filter), &Value::Node(ref node)) if hydrate => {
+                    let mut node = node.clone();
+                    try!(self.hydrate(&mut node, filter));
+                    node.matches(filter)
+                },
+                (&Some(filter), &Value::Map(ref properties)) if hydrate => {
+                    let mut node = Node {id: String::new(), properties: properties.clone()};
+                    try!(self.hydrate(&mut node, filter));
+                    node.matches(filter)
+                },
+                (&Some(filter), _) => value.matches(filter),
+            };
+
+            if matches {
+                list.push(value);
+            }
+        }
+
+        Ok(list)
+    }
+
+    /// Whether a linked node or a map matches the filter of a traversal, as the
+    /// elements of a list do. A missing node never matches.
+    fn matches_filter(&self, node: Option<Node>, filter: &Predicate) -> KakoiResult<bool> {
+        match node {
+            Some(mut node) => {
+                if needs_hydration(filter) {
+                    try!(self.hydrate(&mut node, filter));
+                }
+                Ok(node.matches(filter))
+            },
+            None => Ok(false),
+        }
+    }
+
+    /// Reads the node an element of a list links to, or `None` if it's missing.
+    fn load_element(&self, value: Value, fields: &Option<Vec<&str>>) -> KakoiResult<Option<Value>> {
+        match value {
+            Value::Link(ref id) => node_value(match fields {
+                &Some(ref fields) => self.get_node(Some(id), fields.to_owned()),
+                &None => self.get_full_node(Some(id)),
+            }).map(|node| match node {
+                Value::Null => None,
+                node => Some(node),
+            }),
+            value => Ok(Some(value)),
+        }
+    }
+
+    /// Reads a page of a list that isn't filtered or sorted, only reading the nodes
+    /// of the page. Without cursors, only the page is read from the store. Links to
+    /// missing nodes are dropped after the page is cut, so the page can be shorter
+    /// than its limit even when there are more elements. The cursors are those of
+    /// the links that were read, so paging on from them doesn't skip elements.
+    fn get_list_page(&self, id: &str, fields: Option<&[&str]>, page: &Page) -> KakoiResult<(Vec<Value>, bool, (Value, Value))> {
+        let (list, has_more) = if page.after.is_none() && page.before.is_none() {
+            // One more element than the limit is read to know if there are more.
+            let stop = page.limit.map_or(usize::MAX, |limit| page.offset.saturating_add(limit).saturating_add(1));
+            let mut list: Vec<Value> = try!(self.store.lrange(&list_key(id), page.offset, stop).map_err(Error::Io))
+                .unwrap_or_else(Vec::new)
+                .into_iter()
+                .map(From::from)
+                .collect();
+
+            let has_more = page.limit.map_or(false, |limit| list.len() > limit);
+            if let Some(limit) = page.limit {
+                list.truncate(limit);
+            }
+            (list, has_more)
+        } else {
+            try!(paginate(try!(self.get_list(id)), page))
+        };
+
+        let cursors = cursors(&list);
+        let fields = fields.map(root_fields);
+        let mut loaded = Vec::with_capacity(list.len());
+        for value in list {
+            if let Some(value) = try!(self.load_element(value, &fields)) {
+                loaded.push(value);
+            }
+        }
+
+        Ok((loaded, has_more, cursors))
+    }
+
+    /// Reads a field of an element of a list, without changing the element when the
+    /// field is a path through links.
+    fn element_value(&self, value: &Value, field: &str) -> KakoiResult<Value> {
+        match value {
+            &Value::Node(ref node) if field.contains(predicate::SEPARATOR) => {
+                let mut node = node.clone();
+                try!(self.hydrate_path(&mut node, field, Hydrate::Value));
+                Ok(predicate::field_value(&Value::Node(node), field).clone())
+            },
+            &Value::Map(ref properties) if field.contains(predicate::SEPARATOR) => {
+                let mut node = Node {id: String::new(), properties: properties.clone()};
+                try!(self.hydrate_path(&mut node, field, Hydrate::Value));
+                Ok(predicate::field_value(&Value::Node(node), field).clone())
+            },
+            value => Ok(predicate::field_value(value, field).clone()),
+        }
+    }
+
+    fn aggregate(&self, list: Vec<Value>, aggregate: &Aggregate, group_by: &Option<String>) -> KakoiResult<Value> {
+        let mut groups: Vec<(Value, Vec<Value>)> = Vec::new();
+
+        for element in list {
+            let value = match aggregate.field() {
+                Some(field) => try!(self.element_value(&element, field)),
+                None => Value::Null,
+            };
+            let key = match group_by {
+                &Some(ref field) => try!(self.element_value(&element, field)),
+                &None => Value::Null,
+            };
+
+            match groups.iter().position(|group| group.0.total_cmp(&key) == Ordering::Equal) {
+                Some(index) => groups[index].1.push(value),
+                None => groups.push((key, vec![value])),
+            }
+        }
+
+        if group_by.is_none() {
+            return Ok(aggregate.apply(groups.pop().map_or_else(Vec::new, |group| group.1)));
+        }
+
+        groups.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Value::List(groups
+            .into_iter()
+            .map(|(key, values)| {
+                let mut group = HashMap::new();
+                group.insert(GROUP_KEY.to_string(), key);
+                group.insert(GROUP_VALUE.to_string(), aggregate.apply(values));
+                Value::Map(group)
+            })
+            .collect()))
+    }
+
+    /// Sorts the elements of a list by the keys. The sort is stable, and keys that
+    /// are paths through links are read without changing the returned elements.
+    fn sort(&self, list: Vec<Value>, order_by: &[OrderBy]) -> KakoiResult<Vec<Value>> {
+        let mut keyed = Vec::with_capacity(list.len());
+
+        for value in list {
+            let mut keys = Vec::with_capacity(order_by.len());
+            for key in order_by {
+                keys.push(try!(self.element_value(&value, &key.field)));
+            }
+            keyed.push((keys, value));
+        }
+
+        keyed.sort_by(|a, b| {
+            order_by
                 .iter()
-                .map(|value| {
-                    match value {
-                        &Value::Link(ref id) => match fields {
-                            Some(fields) => self.get_node(Some(id), fields.to_owned()),
-                            None => self.get_full_node(Some(id)),
-                        },
-                        _ => Ok(None),
+                .zip(a.0.iter().zip(b.0.iter()))
+                .map(|(key, (a, b))| compare_keys(key, a, b))
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        Ok(keyed.into_iter().map(|(_, value)| value).collect())
+    }
+
+    /// Loads the linked nodes and lists the predicate needs into the node, so that
+    /// it can be matched in memory.
+    fn hydrate(&self, node: &mut Node, predicate: &Predicate) -> KakoiResult {
+        match predicate {
+            &Predicate::All(ref predicates) | &Predicate::Any(ref predicates) => {
+                for predicate in predicates {
+                    try!(self.hydrate(node, predicate));
+                }
+                Ok(())
+            },
+            &Predicate::Not(ref predicate) => self.hydrate(node, predicate),
+            &Predicate::AnyElement(ref field, ref predicate) |
+            &Predicate::AllElements(ref field, ref predicate) |
+            &Predicate::NoElement(ref field, ref predicate) =>
+                self.hydrate_path(node, field, Hydrate::Elements(predicate)),
+            &Predicate::Count(ref field, _) => self.hydrate_path(node, field, Hydrate::Length),
+            _ => {
+                for field in predicate.get_fields() {
+                    if field.contains(predicate::SEPARATOR) {
+                        try!(self.hydrate_path(node, field, Hydrate::Value));
                     }
-                })
-                .filter_map(|result| {
-                    let node = match result {
-                        Ok(Some(node)) => node,
-                        Ok(None) => return None,
-                        Err(err) => return Some(Err(err)),
-                    };
-
-                    if filter.as_ref().map_or(true, |p| node.matches(p)) {
-                        Some(Ok(node))
-                    } else {
-                        None
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn hydrate_path(&self, node: &mut Node, path: &str, hydrate: Hydrate) -> KakoiResult {
+        let (field, rest) = match path.find(predicate::SEPARATOR) {
+            Some(index) => (&path[..index], Some(&path[index + 1..])),
+            None => (path, None),
+        };
+
+        let value = match (node.properties.get_mut(field), rest, hydrate) {
+            (Some(&mut Value::Link(ref id)), Some(rest), _) => match try!(self.get_full_node(Some(id))) {
+                Some(mut linked) => {
+                    try!(self.hydrate_path(&mut linked, rest, hydrate));
+                    Value::Node(linked)
+                },
+                None => Value::Null,
+            },
+            (Some(&mut Value::Node(ref mut linked)), Some(rest), _) =>
+                return self.hydrate_path(linked, rest, hydrate),
+            (Some(&mut Value::ListLink(ref id)), None, Hydrate::Length) => Value::List(try!(self.get_list(id))),
+            (Some(&mut Value::ListLink(ref id)), None, Hydrate::Elements(predicate)) => {
+                let mut values = try!(self.get_filtered_list(id, None, &None));
+                for value in values.iter_mut() {
+                    if let &mut Value::Node(ref mut element) = value {
+                        try!(self.hydrate(element, predicate));
                     }
-                })
-                .collect()
-        );
+                }
+                Value::List(values)
+            },
+            (Some(&mut Value::List(ref mut values)), None, Hydrate::Elements(predicate)) => {
+                for value in values.iter_mut() {
+                    if let &mut Value::Node(ref mut element) = value {
+                        try!(self.hydrate(element, predicate));
+                    }
+                }
+                return Ok(());
+            },
+            _ => return Ok(()),
+        };
 
-        Ok(list)
+        node.properties.insert(field.to_owned(), value);
+        Ok(())
     }
 
     fn get_node(&self, id: Option<&str>, fields: Vec<&str>) -> KakoiResult<Option<Node>> {
@@ -351,10 +1023,12 @@ impl<'a> Database<'a> {
 mod tests {
     use super::*;
     use std::collections::HashMap;
+    use std::thread;
     use datastore::memory::MemoryDataStore;
-    use entities::{FilteredSelector, KakoiResult, Mutation, MutationOperation, NodeType, PathPart, Selector};
+    use entities::{Error, FilteredSelector, KakoiResult, Mutation, MutationOperation, NodeType, Nulls, OrderBy, Page, PathPart, Selector};
+    use id::{ContentHashIds, CounterIds};
     use node::Node;
-    use predicate::Predicate;
+    use predicate::{ELEMENT, Predicate};
     use value::Value;
 
     fn names(series: Vec<Node>) -> Vec<String> {
@@ -372,7 +1046,7 @@ mod tests {
         let mut node = Node {id: name.to_string(), properties: HashMap::new()};
         node.properties.insert("name".to_string(), Value::String(name.to_string()));
         node.properties.insert("year".to_string(), Value::I64(year));
-        node.properties.insert("episodes".to_string(), Value::List(episodes));
+        node.properties.insert("episodes".to_string(), Value::List(episodes.into_iter().map(Value::Node).collect()));
         node
     }
 
@@ -404,26 +1078,40 @@ mod tests {
         );
 
         db.set(
-            &[PathPart::Field(&"series")],
-            Value::List(vec![elementary, sherlock]),
+            &[PathPart::Field("series".into())],
+            Value::List(vec![Value::Node(elementary), Value::Node(sherlock)]),
         ).unwrap();
 
         db
     }
 
-    fn get_series(result: KakoiResult<HashMap<String, Value>>) -> Vec<Node> {
-        let list = result.unwrap().remove("series").unwrap();
+    fn get_list(result: KakoiResult<HashMap<String, Value>>, field: &str) -> Vec<Value> {
+        let list = result.unwrap().remove(field).unwrap();
         if let Value::List(list) = list {
             list
         } else {
-            panic!("{:?} where returned for series, expected a List", list)
+            panic!("{:?} where returned for {}, expected a List", list, field)
         }
     }
 
+    fn nodes(list: Vec<Value>) -> Vec<Node> {
+        list
+            .into_iter()
+            .map(|value| match value {
+                Value::Node(node) => node,
+                _ => panic!("{:?} where returned in list, expected a Node", value),
+            })
+            .collect()
+    }
+
+    fn get_series(result: KakoiResult<HashMap<String, Value>>) -> Vec<Node> {
+        nodes(get_list(result, "series"))
+    }
+
     fn get_episodes(node: &mut Node) -> Vec<Node> {
         let list = node.properties.remove("episodes").unwrap();
         if let Value::List(list) = list {
-            list
+            nodes(list)
         } else {
             panic!("{:?} where returned for episodes, expected a List", list)
         }
@@ -440,9 +1128,11 @@ mod tests {
         let mut store = MemoryDataStore::new();
         let db = create_db(&mut store);
 
-        let series = get_series(db.select(&Selector::Traverse("series", &FilteredSelector {
-            selector: Selector::Field("name"),
+        let series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::Field("name".into()),
             filter: None,
+            order_by: Vec::new(),
+            page: None,
         })));
 
         assert_eq!(series.len(), 2);
@@ -454,12 +1144,16 @@ mod tests {
         let mut store = MemoryDataStore::new();
         let db = create_db(&mut store);
 
-        let mut series = get_series(db.select(&Selector::Traverse("series", &FilteredSelector {
-            selector: Selector::Traverse("episodes", &FilteredSelector {
-                selector: Selector::Field("name"),
+        let mut series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::traverse("episodes", FilteredSelector {
+                selector: Selector::Field("name".into()),
                 filter: None,
+                order_by: Vec::new(),
+                page: None,
             }),
             filter: None,
+            order_by: Vec::new(),
+            page: None,
         })));
 
         assert_eq!(series.len(), 2);
@@ -470,26 +1164,45 @@ mod tests {
         assert_eq!(names(episodes), ["Pilot", "While You Were Sleeping"]);
     }
 
+    #[test]
+    fn select_multi_traverse() {
+        let mut store = MemoryDataStore::new();
+        let db = create_db(&mut store);
+
+        let mut series = get_series(db.select(&Selector::traverse("series", Selector::Multi(vec![
+            Selector::field("name"),
+            Selector::traverse("episodes", Selector::field("name").filter(Predicate::StartsWith("name".into(), "The".into()))),
+        ]))));
+
+        assert_eq!(names(get_episodes(&mut series[0])), Vec::<String>::new());
+        assert_eq!(names(get_episodes(&mut series[1])), ["The Blind Banker"]);
+        assert_eq!(names(series), ["Elementary", "Sherlock"]);
+    }
+
     #[test]
     fn set() {
         let mut store = MemoryDataStore::new();
         let mut db = create_db(&mut store);
 
         db.mutate(Mutation {
-            path: &[
-                PathPart::FieldFilter("series", Predicate::Eq("name", "Sherlock".into())),
-                PathPart::Field("episodes"),
-                PathPart::Field("name"),
+            path: vec![
+                PathPart::FieldFilter("series".into(), Predicate::Eq("name".into(), "Sherlock".into())),
+                PathPart::Field("episodes".into()),
+                PathPart::Field("name".into()),
             ],
             opertaion: MutationOperation::Set(Value::String("Name".into())),
         }).unwrap();
 
-        let mut series = get_series(db.select(&Selector::Traverse("series", &FilteredSelector {
-            selector: Selector::Traverse("episodes", &FilteredSelector {
-                selector: Selector::Field("name"),
+        let mut series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::traverse("episodes", FilteredSelector {
+                selector: Selector::Field("name".into()),
                 filter: None,
+                order_by: Vec::new(),
+                page: None,
             }),
             filter: None,
+            order_by: Vec::new(),
+            page: None,
         })));
 
         assert_eq!(series.len(), 2);
@@ -516,13 +1229,15 @@ mod tests {
         holmes.insert("name".to_string(), Value::String("Holmes".into()));
 
         db.mutate(Mutation {
-            path: &[PathPart::FieldFilter("series", Predicate::Eq("name", "Sherlock".into()))],
+            path: vec![PathPart::FieldFilter("series".into(), Predicate::Eq("name".into(), "Sherlock".into()))],
             opertaion: MutationOperation::Merge(holmes),
         }).unwrap();
 
-        let series = get_series(db.select(&Selector::Traverse("series", &FilteredSelector {
-            selector: Selector::Field("name"),
+        let series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::Field("name".into()),
             filter: None,
+            order_by: Vec::new(),
+            page: None,
         })));
 
         assert_eq!(series.len(), 2);
@@ -537,16 +1252,873 @@ mod tests {
         let sherlock_homes = serie("Sherlock Holmes", 1984, Vec::new());
 
         db.mutate(Mutation {
-            path: &[PathPart::Field("series")],
+            path: vec![PathPart::Field("series".into())],
             opertaion: MutationOperation::Append(NodeType::Node(sherlock_homes)),
         }).unwrap();
 
-        let series = get_series(db.select(&Selector::Traverse("series", &FilteredSelector {
-            selector: Selector::Field("name"),
+        let series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::Field("name".into()),
             filter: None,
+            order_by: Vec::new(),
+            page: None,
         })));
 
         assert_eq!(series.len(), 3);
         assert_eq!(names(series), ["Elementary", "Sherlock", "Sherlock Holmes"]);
     }
+
+    #[test]
+    fn scalar_list() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        db.set(
+            &[PathPart::Field("tags".into())],
+            Value::List(vec![Value::String("drama".into()), Value::String("crime".into())]),
+        ).unwrap();
+
+        db.mutate(Mutation {
+            path: vec![PathPart::Field("tags".into())],
+            opertaion: MutationOperation::Append(NodeType::Values(vec![Value::I64(42), Value::Null])),
+        }).unwrap();
+
+        let tags = get_list(db.select(&Selector::traverse("tags", FilteredSelector {
+            selector: Selector::AllFields,
+            filter: None,
+            order_by: Vec::new(),
+            page: None,
+        })), "tags");
+
+        assert_eq!(tags, [
+            Value::String("drama".into()),
+            Value::String("crime".into()),
+            Value::I64(42),
+            Value::Null,
+        ]);
+
+        let tags = get_list(db.select(&Selector::traverse("tags", FilteredSelector {
+            selector: Selector::AllFields,
+            filter: Some(Predicate::Eq(ELEMENT.into(), "crime".into())),
+            order_by: Vec::new(),
+            page: None,
+        })), "tags");
+
+        assert_eq!(tags, [Value::String("crime".into())]);
+    }
+
+    #[test]
+    fn mixed_list() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        db.set(
+            &[PathPart::Field("mixed".into())],
+            Value::List(vec![Value::Node(episode("Pilot")), Value::F64(1.5), Value::Boolean(true)]),
+        ).unwrap();
+
+        let mut mixed = get_list(db.select(&Selector::traverse("mixed", FilteredSelector {
+            selector: Selector::Field("name".into()),
+            filter: None,
+            order_by: Vec::new(),
+            page: None,
+        })), "mixed");
+
+        assert_eq!(mixed.len(), 3);
+        assert_eq!(mixed.split_off(1), [Value::F64(1.5), Value::Boolean(true)]);
+        assert_eq!(names(nodes(mixed)), ["Pilot"]);
+    }
+
+    #[test]
+    fn map() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let mut size = HashMap::new();
+        size.insert("width".to_string(), Value::I64(1920));
+        size.insert("height".to_string(), Value::F64(1080.5));
+
+        let mut address = HashMap::new();
+        address.insert("street".to_string(), Value::String("221B Baker Street".into()));
+        address.insert("city:country".to_string(), Value::String("London:UK".into()));
+        address.insert("size".to_string(), Value::Map(size.clone()));
+        address.insert("owner".to_string(), Value::Node(episode("Mrs Hudson")));
+
+        db.set(&[PathPart::Field("address".into())], Value::Map(address.clone())).unwrap();
+
+        let mut selected = db.select(&Selector::Field("address".into())).unwrap();
+        address.insert("owner".to_string(), Value::Link("Mrs Hudson".into()));
+        assert_eq!(selected.remove("address"), Some(Value::Map(address)));
+
+        let mut selected = db.select(&Selector::traverse("address", FilteredSelector {
+            selector: Selector::Multi(vec![
+                Selector::Field("street".into()),
+                Selector::traverse("size", FilteredSelector {
+                    selector: Selector::Field("width".into()),
+                    filter: None,
+                    order_by: Vec::new(),
+                    page: None,
+                }),
+                Selector::traverse("owner", FilteredSelector {
+                    selector: Selector::Field("name".into()),
+                    filter: None,
+                    order_by: Vec::new(),
+                    page: None,
+                }),
+            ]),
+            filter: None,
+            order_by: Vec::new(),
+            page: None,
+        })).unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert("street".to_string(), Value::String("221B Baker Street".into()));
+        size.remove("height");
+        expected.insert("size".to_string(), Value::Map(size));
+        expected.insert("owner".to_string(), Value::Node(episode("Mrs Hudson")));
+
+        assert_eq!(selected.remove("address"), Some(Value::Map(expected)));
+    }
+
+    #[test]
+    fn map_list() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let address = |city: &str, zip: i64, owner: &str| {
+            let mut address = HashMap::new();
+            address.insert("city".to_string(), Value::String(city.to_string()));
+            address.insert("zip".to_string(), Value::I64(zip));
+            address.insert("owner".to_string(), Value::Node(episode(owner)));
+            Value::Map(address)
+        };
+        db.set(&[PathPart::field("addrs")], Value::List(vec![
+            address("London", 1, "Mrs Hudson"),
+            address("Paris", 2, "Irene"),
+            address("London", 3, "Mycroft"),
+        ])).unwrap();
+
+        let select = |db: &Database, query: &str| db.select(&query.parse().unwrap()).unwrap().remove("addrs").unwrap();
+        let city = |city: &str, zip: i64| Value::Map(vec![
+            ("city".to_string(), Value::String(city.to_string())),
+            ("zip".to_string(), Value::I64(zip)),
+        ].into_iter().collect());
+
+        assert_eq!(
+            select(&db, "{ addrs(city == \"London\") order by zip desc { city zip } }"),
+            Value::List(vec![city("London", 3), city("London", 1)])
+        );
+        assert_eq!(
+            select(&db, "{ addrs(owner.name ^= \"M\") order by owner.name { city zip } }"),
+            Value::List(vec![city("London", 1), city("London", 3)])
+        );
+        assert_eq!(select(&db, "{ addrs { @sum(zip) } }"), Value::I64(6));
+        assert_eq!(
+            select(&db, "{ addrs(zip > 1) { owner { name } } }"),
+            Value::List(vec![
+                Value::Map(vec![("owner".to_string(), Value::Node(episode("Irene")))].into_iter().collect()),
+                Value::Map(vec![("owner".to_string(), Value::Node(episode("Mycroft")))].into_iter().collect()),
+            ])
+        );
+    }
+
+    #[test]
+    fn migrate() {
+        let mut store = MemoryDataStore::new();
+
+        {
+            let mut legacy = HashMap::new();
+            legacy.insert("name".to_string(), PrimitiveValue::from("SSherlock"));
+            legacy.insert("year".to_string(), PrimitiveValue::from(2010));
+            legacy.insert("episodes".to_string(), PrimitiveValue::from("lepisodes"));
+            store.hset_all("root", &legacy).unwrap();
+            store.lpush("list_episodes", &vec!["Lpilot".into(), "Raw".into()]).unwrap();
+            store.hset("node_pilot", "name", &"SA Study in Pink".into()).unwrap();
+        }
+
+        {
+            let mut db = Database::new(&mut store);
+            assert_eq!(db.migrate().unwrap(), 4);
+            assert_eq!(db.migrate().unwrap(), 0);
+
+            let mut selected = db.select(&Selector::Multi(vec![
+                Selector::Field("name".into()),
+                Selector::traverse("episodes", FilteredSelector {
+                    selector: Selector::Field("name".into()),
+                    filter: None,
+                    order_by: Vec::new(),
+                    page: None,
+                }),
+            ])).unwrap();
+
+            assert_eq!(selected.remove("name"), Some(Value::String("Sherlock".into())));
+
+            let mut episodes = selected.remove("episodes").map(|episodes| match episodes {
+                Value::List(list) => list,
+                _ => panic!("{:?} where returned for episodes, expected a List", episodes),
+            }).unwrap();
+
+            assert_eq!(episodes.pop(), Some(Value::String("Raw".into())));
+            assert_eq!(names(nodes(episodes)), ["A Study in Pink"]);
+        }
+
+        assert_eq!(store.hget("root", vec!["name"]).unwrap().unwrap()["name"],
+                   PrimitiveValue::String(encoding::encode(&Value::String("Sherlock".into())).unwrap()));
+    }
+
+    #[test]
+    fn migrate_raw_strings() {
+        let mut store = MemoryDataStore::new();
+        let raw = ["Madrid", "index", "union", "false", "bbc", "nobody"];
+
+        {
+            let mut legacy = HashMap::new();
+            for string in raw.iter() {
+                legacy.insert(string.to_string(), PrimitiveValue::from(*string));
+            }
+            legacy.insert("tags".to_string(), PrimitiveValue::from("ltags"));
+            store.hset_all("root", &legacy).unwrap();
+            store.lpush("list_tags", &raw.iter().map(|string| PrimitiveValue::from(*string)).collect()).unwrap();
+        }
+
+        let mut db = Database::new(&mut store);
+        assert_eq!(db.migrate().unwrap(), raw.len() + 2);
+
+        let mut selected = db.select(&Selector::AllFields).unwrap();
+        for string in raw.iter() {
+            assert_eq!(selected.remove(*string), Some(Value::String(string.to_string())));
+        }
+
+        let tags = db.select(&Selector::traverse("tags", Selector::AllFields)).unwrap().remove("tags");
+        assert_eq!(tags, Some(Value::List(raw.iter().map(|string| Value::String(string.to_string())).collect())));
+    }
+
+    #[test]
+    fn merge_node() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let mut properties = HashMap::new();
+        properties.insert("pilot".to_string(), Value::Node(episode("Pilot")));
+
+        db.mutate(Mutation {
+            path: vec![PathPart::FieldFilter("series".into(), Predicate::Eq("name".into(), "Elementary".into()))],
+            opertaion: MutationOperation::Merge(properties),
+        }).unwrap();
+
+        let series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::traverse("pilot", FilteredSelector {
+                selector: Selector::Field("name".into()),
+                filter: None,
+                order_by: Vec::new(),
+                page: None,
+            }),
+            filter: Some(Predicate::Eq("name".into(), "Elementary".into())),
+            order_by: Vec::new(),
+            page: None,
+        })));
+
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].properties["pilot"], Value::Node(episode("Pilot")));
+    }
+
+    #[test]
+    fn invalid_value() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let result = db.set(
+            &[PathPart::Field("tags".into())],
+            Value::List(vec![Value::String("drama".into()), Value::Error("error".into())]),
+        );
+        assert!(matches!(result, Err(Error::InvalidValue(_))), "{:?} is InvalidValue", result);
+
+        let mut properties = HashMap::new();
+        properties.insert("error".to_string(), Value::Error("error".into()));
+        let result = db.merge(&[PathPart::Field("series".into())], properties);
+        assert!(matches!(result, Err(Error::InvalidValue(_))), "{:?} is InvalidValue", result);
+
+        let selected = db.select(&Selector::Field("tags".into())).unwrap();
+        assert_eq!(selected["tags"], Value::Null);
+    }
+
+    #[test]
+    fn export_import() {
+        let mut store = MemoryDataStore::new();
+        let exported = {
+            let mut db = create_db(&mut store);
+            db.set(&[PathPart::Field("up_next".into())], Value::Link("Pilot".into())).unwrap();
+            db.export().unwrap()
+        };
+
+        assert_eq!(exported["up_next"], Value::Link("Pilot".into()));
+
+        let mut other_store = MemoryDataStore::new();
+        let mut db = Database::new(&mut other_store);
+        db.import(exported.clone()).unwrap();
+
+        assert_eq!(db.export().unwrap(), exported);
+        assert_eq!(names(get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::Field("name".into()),
+            filter: None,
+            order_by: Vec::new(),
+            page: None,
+        })))), ["Elementary", "Sherlock"]);
+    }
+
+    #[test]
+    fn counter_ids() {
+        let mut store = MemoryDataStore::new();
+        let mut db = Database::new(&mut store).with_id_generator(CounterIds::new());
+
+        let mut untitled = episode("Untitled");
+        untitled.id = String::new();
+
+        db.set(&[PathPart::Field("episodes".into())], Value::List(vec![
+            Value::Node(untitled.clone()),
+            Value::Node(episode("Pilot")),
+            Value::Node(untitled.clone()),
+        ])).unwrap();
+
+        let episodes = nodes(get_list(db.select(&Selector::traverse("episodes", FilteredSelector {
+            selector: Selector::AllFields,
+            filter: None,
+            order_by: Vec::new(),
+            page: None,
+        })), "episodes"));
+
+        let ids: Vec<&str> = episodes.iter().map(|node| node.id.as_ref()).collect();
+        assert_eq!(ids, ["1", "Pilot", "2"]);
+
+        assert_eq!(db.select(&Selector::Field("episodes".into())).unwrap()["episodes"], Value::ListLink("3".into()));
+    }
+
+    #[test]
+    fn content_hash_ids() {
+        let mut store = MemoryDataStore::new();
+        let mut db = Database::new(&mut store).with_id_generator(ContentHashIds);
+
+        let mut untitled = episode("Untitled");
+        untitled.id = String::new();
+        let episodes = Value::List(vec![Value::Node(untitled.clone()), Value::Node(untitled)]);
+
+        db.set(&[PathPart::Field("episodes".into())], episodes.clone()).unwrap();
+        db.set(&[PathPart::Field("reruns".into())], episodes).unwrap();
+
+        let selected = db.select(&Selector::Multi(vec![Selector::Field("episodes".into()), Selector::Field("reruns".into())])).unwrap();
+        assert_eq!(selected["episodes"], selected["reruns"]);
+
+        let episodes = nodes(get_list(db.select(&Selector::traverse("episodes", FilteredSelector {
+            selector: Selector::AllFields,
+            filter: None,
+            order_by: Vec::new(),
+            page: None,
+        })), "episodes"));
+
+        assert_eq!(episodes.len(), 2);
+        assert_eq!(episodes[0], episodes[1]);
+        assert!(!episodes[0].id.is_empty());
+    }
+
+    #[test]
+    fn content_hash_ids_append() {
+        let mut store = MemoryDataStore::new();
+        let mut db = Database::new(&mut store).with_id_generator(ContentHashIds);
+
+        db.set(&[PathPart::Field("a".into())], Value::List(vec![Value::String("x".into())])).unwrap();
+        db.append(&[PathPart::Field("a".into())], NodeType::Value(Value::String("y".into()))).unwrap();
+        db.set(&[PathPart::Field("b".into())], Value::List(vec![Value::String("x".into())])).unwrap();
+        db.set(&[PathPart::Field("c".into())], Value::List(vec![Value::String("x".into())])).unwrap();
+
+        assert_eq!(get_list(db.select(&Selector::traverse("a", Selector::AllFields)), "a"), [
+            Value::String("x".into()),
+            Value::String("y".into()),
+        ]);
+        assert_eq!(get_list(db.select(&Selector::traverse("b", Selector::AllFields)), "b"), [Value::String("x".into())]);
+        assert_eq!(get_list(db.select(&Selector::traverse("c", Selector::AllFields)), "c"), [Value::String("x".into())]);
+    }
+
+    #[test]
+    fn counter_ids_skip_stored() {
+        let mut store = MemoryDataStore::new();
+        let mut untitled = episode("Untitled");
+        untitled.id = String::new();
+
+        {
+            let mut db = Database::new(&mut store).with_id_generator(CounterIds::new());
+            db.set(&[PathPart::Field("a".into())], Value::List(vec![Value::I64(1)])).unwrap();
+            db.set(&[PathPart::Field("pilot".into())], Value::Node(untitled.clone())).unwrap();
+        }
+
+        let mut db = Database::new(&mut store).with_id_generator(CounterIds::new());
+        untitled.properties.insert("name".into(), Value::String("Other".into()));
+        db.set(&[PathPart::Field("b".into())], Value::List(vec![Value::I64(9)])).unwrap();
+        db.set(&[PathPart::Field("other".into())], Value::Node(untitled)).unwrap();
+
+        assert_eq!(get_list(db.select(&Selector::traverse("a", Selector::AllFields)), "a"), [Value::I64(1)]);
+        assert_eq!(get_list(db.select(&Selector::traverse("b", Selector::AllFields)), "b"), [Value::I64(9)]);
+
+        let selected = db.select(&Selector::Multi(vec![
+            Selector::traverse("pilot", Selector::AllFields),
+            Selector::traverse("other", Selector::AllFields),
+        ])).unwrap();
+        let names: Vec<Value> = ["pilot", "other"].iter()
+            .map(|field| match selected[*field] {
+                Value::Node(ref node) => node.properties["name"].clone(),
+                ref value => panic!("{:?} where returned for {}, expected a Node", value, field),
+            })
+            .collect();
+        assert_eq!(names, [Value::String("Untitled".into()), Value::String("Other".into())]);
+    }
+
+    #[test]
+    fn missing_field() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        db.mutate(Mutation {
+            path: vec![PathPart::Field("series".into())],
+            opertaion: MutationOperation::Append(NodeType::Node(episode("Untitled"))),
+        }).unwrap();
+
+        let series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::Field("name".into()),
+            filter: Some(Predicate::Gt("year".into(), 2000.into())),
+            order_by: Vec::new(),
+            page: None,
+        })));
+        assert_eq!(names(series), ["Elementary", "Sherlock"]);
+
+        let series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::Field("name".into()),
+            filter: Some(Predicate::IsNull("year".into())),
+            order_by: Vec::new(),
+            page: None,
+        })));
+        assert_eq!(names(series), ["Untitled"]);
+
+        let mut series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::traverse("episodes", FilteredSelector {
+                selector: Selector::Field("name".into()),
+                filter: None,
+                order_by: Vec::new(),
+                page: None,
+            }),
+            filter: Some(Predicate::Exists("name".into())),
+            order_by: Vec::new(),
+            page: None,
+        })));
+        assert_eq!(series.len(), 3);
+        assert_eq!(series.pop().unwrap().properties["episodes"], Value::Null);
+    }
+
+    #[test]
+    fn string_filters() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        db.mutate(Mutation {
+            path: vec![
+                PathPart::FieldFilter("series".into(), Predicate::StartsWith("name".into(), "Sher".into())),
+                PathPart::FieldFilter("episodes".into(), Predicate::regex("name", "(?i)^the ").unwrap()),
+                PathPart::Field("name".into()),
+            ],
+            opertaion: MutationOperation::Set(Value::String("Name".into())),
+        }).unwrap();
+
+        let mut series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::traverse("episodes", FilteredSelector {
+                selector: Selector::Field("name".into()),
+                filter: Some(Predicate::Contains("name".into(), "a".into())),
+                order_by: Vec::new(),
+                page: None,
+            }),
+            filter: Some(Predicate::EqIgnoreCase("name".into(), "sherlock".into())),
+            order_by: Vec::new(),
+            page: None,
+        })));
+
+        assert_eq!(series.len(), 1);
+        assert_eq!(names(get_episodes(&mut series[0])), ["Name"]);
+    }
+
+    #[test]
+    fn nested_filters() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let mut bbc = episode("BBC");
+        bbc.properties.insert("country".to_string(), Value::String("UK".into()));
+        db.set(&[
+            PathPart::FieldFilter("series".into(), Predicate::Eq("name".into(), "Sherlock".into())),
+            PathPart::Field("network".into()),
+        ], Value::Node(bbc)).unwrap();
+        db.set(&[
+            PathPart::FieldFilter("series".into(), Predicate::Eq("name".into(), "Elementary".into())),
+            PathPart::Field("network".into()),
+        ], Value::Node(episode("CBS"))).unwrap();
+
+        let series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::Multi(vec![Selector::Field("name".into()), Selector::Field("network".into())]),
+            filter: Some(Predicate::Eq("network.country".into(), "UK".into())),
+            order_by: Vec::new(),
+            page: None,
+        })));
+
+        assert_eq!(names(series.clone()), ["Sherlock"]);
+        assert_eq!(series[0].properties.get("network"), Some(&Value::Link("BBC".into())));
+
+        db.set(&[
+            PathPart::FieldFilter("series".into(), Predicate::Neq("network.name".into(), "BBC".into())),
+            PathPart::Field("name".into()),
+        ], Value::String("CBS show".into())).unwrap();
+
+        let series = get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::Field("name".into()),
+            filter: None,
+            order_by: Vec::new(),
+            page: None,
+        })));
+
+        assert_eq!(names(series), ["CBS show", "Sherlock"]);
+    }
+
+    #[test]
+    fn quantifier_filters() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let select = |db: &Database, filter: Predicate| names(get_series(db.select(&Selector::traverse("series", FilteredSelector {
+            selector: Selector::Field("name".into()),
+            filter: Some(filter),
+            order_by: Vec::new(),
+            page: None,
+        }))));
+
+        assert_eq!(select(&db, Predicate::AnyElement("episodes".into(), Box::new(Predicate::Eq("name".into(), "Pilot".into())))), ["Elementary"]);
+        assert_eq!(select(&db, Predicate::AllElements("episodes".into(), Box::new(Predicate::StartsWith("name".into(), "Th".into())))), Vec::<String>::new());
+        assert_eq!(select(&db, Predicate::NoElement("episodes".into(), Box::new(Predicate::Eq("name".into(), "Pilot".into())))), ["Sherlock"]);
+        assert_eq!(select(&db, Predicate::Count("episodes".into(), Box::new(Predicate::Gt(ELEMENT.into(), 1.into())))), ["Elementary", "Sherlock"]);
+        assert_eq!(select(&db, Predicate::All(vec![
+            Predicate::AnyElement("episodes".into(), Box::new(Predicate::Contains("name".into(), "Pink".into()))),
+            Predicate::AnyElement("episodes".into(), Box::new(Predicate::Contains("name".into(), "Banker".into()))),
+        ])), ["Sherlock"]);
+
+        db.set(&[
+            PathPart::FieldFilter("series".into(), Predicate::AnyElement("episodes".into(), Box::new(Predicate::Eq("name".into(), "Pilot".into())))),
+            PathPart::Field("year".into()),
+        ], Value::I64(2013)).unwrap();
+
+        assert_eq!(select(&db, Predicate::Eq("year".into(), 2013.into())), ["Elementary"]);
+    }
+
+    #[test]
+    fn owned_queries() {
+        let mut store = MemoryDataStore::new();
+        let db = create_db(&mut store);
+
+        let selector = thread::spawn(|| {
+            let mut filter = Predicate::Any(Vec::new());
+            for name in vec!["Pilot", "The Blind Banker"] {
+                filter = filter.or(Predicate::Eq("name".into(), name.into()));
+            }
+
+            Selector::traverse("series", Selector::traverse("episodes", Selector::field("name").filter(filter)))
+        }).join().unwrap();
+
+        let mut series = get_series(db.select(&selector));
+
+        assert_eq!(names(get_episodes(&mut series[0])), ["Pilot"]);
+        assert_eq!(names(get_episodes(&mut series[1])), ["The Blind Banker"]);
+    }
+
+    #[test]
+    fn order_by() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        db.mutate_path("series", MutationOperation::Append(NodeType::Node(serie("Luther", 2010, vec![])))).unwrap();
+        db.mutate_path("series", MutationOperation::Append(NodeType::Node(episode("Untitled")))).unwrap();
+        db.mutate_path("series[name == \"Sherlock\"].network", MutationOperation::Set(Value::Node(episode("BBC")))).unwrap();
+        db.mutate_path("series[name == \"Elementary\"].network", MutationOperation::Set(Value::Node(episode("CBS")))).unwrap();
+
+        let select = |db: &Database, selector: FilteredSelector| names(get_series(db.select(&Selector::traverse("series", selector))));
+
+        assert_eq!(
+            select(&db, Selector::field("name").order_by(OrderBy::desc("year")).order_by(OrderBy::asc("name"))),
+            ["Elementary", "Luther", "Sherlock", "Untitled"]
+        );
+        assert_eq!(
+            select(&db, Selector::field("name").order_by(OrderBy::asc("year").nulls(Nulls::First))),
+            ["Untitled", "Sherlock", "Luther", "Elementary"]
+        );
+        assert_eq!(
+            select(&db, Selector::field("name").filter(Predicate::Exists("year".into())).order_by(OrderBy::desc("network.name"))),
+            ["Elementary", "Sherlock", "Luther"]
+        );
+
+        let series = get_series(db.select(&Selector::traverse("series", Selector::field("name")
+            .filter(Predicate::Gt("year".into(), 2010.into()))
+            .order_by(OrderBy::desc("network.name")))));
+        assert_eq!(series.iter().map(|series| series.properties.len()).collect::<Vec<_>>(), [1]);
+
+        let series = get_series(db.select(&"{ series order by network.name { network } }".parse().unwrap()));
+        assert_eq!(series[0].properties.get("network"), Some(&Value::Link("BBC".into())));
+
+        let tag = |tag: &str| Value::String(tag.to_string());
+        db.set(&[PathPart::field("tags")], Value::List(vec![tag("b"), Value::I64(1), tag("c"), tag("a")])).unwrap();
+        assert_eq!(
+            get_list(db.select(&"{ tags order by $ desc }".parse().unwrap()), "tags"),
+            [tag("c"), tag("b"), tag("a"), Value::I64(1)]
+        );
+    }
+
+    #[test]
+    fn pagination() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        db.mutate_path("series", MutationOperation::Append(NodeType::Node(serie("Luther", 2010, vec![])))).unwrap();
+        db.mutate_path("series", MutationOperation::Append(NodeType::Node(episode("Untitled")))).unwrap();
+
+        let select = |db: &Database, selector: FilteredSelector| {
+            let mut page = match db.select(&Selector::traverse("series", selector)).unwrap().remove("series") {
+                Some(Value::Map(page)) => page,
+                value => panic!("{:?} where returned for series, expected a Map", value),
+            };
+            let has_more = page.remove(PAGE_HAS_MORE).unwrap();
+            let end_cursor = page.remove(PAGE_END_CURSOR).unwrap();
+
+            (names(nodes(get_list(Ok(page), PAGE_ELEMENTS))), has_more, end_cursor)
+        };
+        let name = |name: &str| Value::String(name.to_string());
+
+        assert_eq!(
+            select(&db, Selector::field("name").page(Page::new().limit(2))),
+            (vec!["Elementary".to_string(), "Sherlock".to_string()], Value::Boolean(true), name("Sherlock"))
+        );
+        assert_eq!(select(&db, Selector::field("name").page(Page::new().limit(2).after("Sherlock"))).0, ["Luther", "Untitled"]);
+        assert_eq!(select(&db, Selector::field("name").page(Page::new().limit(2).offset(3))).1, Value::Boolean(false));
+        assert_eq!(select(&db, Selector::field("name").page(Page::new().offset(4))).2, Value::Null);
+        assert_eq!(
+            select(&db, Selector::field("name").page(Page::new().limit(2).before("Untitled"))),
+            (vec!["Sherlock".to_string(), "Luther".to_string()], Value::Boolean(true), name("Luther"))
+        );
+        assert_eq!(
+            select(&db, Selector::field("name").order_by(OrderBy::asc("name")).page(Page::new().limit(2).after("Luther"))),
+            (vec!["Sherlock".to_string(), "Untitled".to_string()], Value::Boolean(false), name("Untitled"))
+        );
+        assert_eq!(
+            select(&db, Selector::field("name").filter(Predicate::Exists("year".into())).page(Page::new().limit(1).offset(1))).0,
+            ["Sherlock"]
+        );
+
+        let result = db.select(&Selector::traverse("series", Selector::AllFields.page(Page::new().after("Moriarty"))));
+        assert!(matches!(result, Err(Error::InvalidQuery(_))), "{:?} is InvalidQuery", result);
+
+        let series = match db.select(&"{ series limit 1 { episodes offset 1 { name } } }".parse().unwrap()).unwrap().remove("series") {
+            Some(Value::Map(page)) => nodes(get_list(Ok(page), PAGE_ELEMENTS)),
+            value => panic!("{:?} where returned for series, expected a Map", value),
+        };
+        assert_eq!(series[0].properties.get("episodes").unwrap(), &Value::Map(vec![
+            (PAGE_ELEMENTS.to_string(), Value::List(vec![Value::Node(episode("While You Were Sleeping"))])),
+            (PAGE_HAS_MORE.to_string(), Value::Boolean(false)),
+            (PAGE_START_CURSOR.to_string(), name("While You Were Sleeping")),
+            (PAGE_END_CURSOR.to_string(), name("While You Were Sleeping")),
+        ].into_iter().collect()));
+    }
+
+    #[test]
+    fn short_pages() {
+        let mut store = MemoryDataStore::new();
+        store.hset("root", "series", &"lseries".into()).unwrap();
+        store.lpush("list_series", &vec![
+            "Lsherlock".into(), "Lmissing".into(), "Lgone".into(), "Lvanished".into(), "Lluther".into(),
+        ]).unwrap();
+        store.hset("node_sherlock", "name", &"SSherlock".into()).unwrap();
+        store.hset("node_luther", "name", &"SLuther".into()).unwrap();
+
+        let db = Database::new(&mut store);
+        let select = |query: &str| match db.select(&query.parse().unwrap()).unwrap().remove("series") {
+            Some(Value::Map(mut page)) => (
+                page.remove(PAGE_HAS_MORE).unwrap(),
+                page.remove(PAGE_END_CURSOR).unwrap(),
+                names(nodes(get_list(Ok(page), PAGE_ELEMENTS))),
+            ),
+            value => panic!("{:?} where returned for series, expected a Map", value),
+        };
+
+        assert_eq!(
+            select("{ series limit 2 { name } }"),
+            (Value::Boolean(true), Value::String("missing".into()), vec!["Sherlock".to_string()])
+        );
+        assert_eq!(
+            select("{ series limit 2 after \"missing\" { name } }"),
+            (Value::Boolean(true), Value::String("vanished".into()), Vec::new())
+        );
+        assert_eq!(
+            select("{ series limit 2 after \"vanished\" { name } }"),
+            (Value::Boolean(false), Value::String("luther".into()), vec!["Luther".to_string()])
+        );
+    }
+
+    #[test]
+    fn aggregates() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        db.mutate_path("series", MutationOperation::Append(NodeType::Node(serie("Luther", 2010, vec![])))).unwrap();
+        db.mutate_path("series[name == \"Sherlock\"].network", MutationOperation::Set(Value::Node(episode("BBC")))).unwrap();
+        db.mutate_path("series[name == \"Luther\"].network", MutationOperation::Set(Value::Link("BBC".into()))).unwrap();
+        db.mutate_path("series[name == \"Elementary\"].network", MutationOperation::Set(Value::Node(episode("CBS")))).unwrap();
+
+        let select = |db: &Database, query: &str| db.select(&query.parse().unwrap()).unwrap().remove("series").unwrap();
+        let group = |key: Value, value: Value| Value::Map(vec![
+            (GROUP_KEY.to_string(), key),
+            (GROUP_VALUE.to_string(), value),
+        ].into_iter().collect());
+
+        assert_eq!(select(&db, "{ series { @avg(year) } }"), Value::F64(6032.0 / 3.0));
+        assert_eq!(select(&db, "{ series(year < 2012) { @count() } }"), Value::U64(2));
+        assert_eq!(select(&db, "{ series { @max(network.name) } }"), Value::String("CBS".into()));
+        assert_eq!(select(&db, "{ series { @distinct(year) } }"), Value::List(vec![Value::I64(2012), Value::I64(2010)]));
+        assert_eq!(select(&db, "{ series { @count() by year } }"), Value::List(vec![
+            group(Value::I64(2010), Value::U64(2)),
+            group(Value::I64(2012), Value::U64(1)),
+        ]));
+        assert_eq!(select(&db, "{ series { @sum(year) by network.name } }"), Value::List(vec![
+            group(Value::String("BBC".into()), Value::I64(4020)),
+            group(Value::String("CBS".into()), Value::I64(2012)),
+        ]));
+
+        let series = get_series(db.select(&"{ series { name episodes { @count() } } }".parse().unwrap()));
+        let counts: Vec<Option<&Value>> = series.iter().map(|series| series.properties.get("episodes")).collect();
+        assert_eq!(counts, [Some(&Value::U64(2)), Some(&Value::U64(2)), Some(&Value::U64(0))]);
+
+        let result = db.select(&Selector::aggregate(Aggregate::Count));
+        assert!(matches!(result, Err(Error::InvalidQuery(_))), "{:?} is InvalidQuery", result);
+    }
+
+    #[test]
+    fn nested_selectors() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let mut bbc = episode("BBC");
+        bbc.properties.insert("country".to_string(), Value::String("UK".into()));
+        db.mutate_path("series[name == \"Sherlock\"].network", MutationOperation::Set(Value::Node(bbc))).unwrap();
+        db.set(&[PathPart::field("title")], Value::String("Detectives".into())).unwrap();
+
+        let mut selected = db.select(&Selector::Multi(vec![
+            Selector::Multi(vec![Selector::field("title")]),
+            Selector::traverse("series", Selector::Multi(vec![
+                Selector::field("name"),
+                Selector::Multi(vec![
+                    Selector::traverse("episodes", Selector::Multi(vec![Selector::Multi(vec![Selector::field("name")])])),
+                    Selector::traverse("network", Selector::Multi(vec![Selector::field("name"), Selector::Multi(vec![Selector::field("country")])])),
+                ]),
+            ]).filter(Predicate::Eq("name".into(), "Sherlock".into()))),
+        ])).unwrap();
+
+        assert_eq!(selected.remove("title"), Some(Value::String("Detectives".into())));
+
+        let mut series = get_series(Ok(selected));
+        assert_eq!(names(get_episodes(&mut series[0])), ["A Study in Pink", "The Blind Banker"]);
+
+        let mut network = HashMap::new();
+        network.insert("name".to_string(), Value::String("BBC".into()));
+        network.insert("country".to_string(), Value::String("UK".into()));
+        assert_eq!(series[0].properties.remove("network"), Some(Value::Node(Node {id: "BBC".into(), properties: network})));
+        assert_eq!(series[0].properties.keys().collect::<Vec<_>>(), ["name"]);
+
+        let result = db.select(&Selector::Multi(vec![Selector::Multi(vec![Selector::aggregate(Aggregate::Count)])]));
+        assert!(matches!(result, Err(Error::InvalidQuery(_))), "{:?} is InvalidQuery", result);
+    }
+
+    #[test]
+    fn filtered_links() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let mut bbc = episode("BBC");
+        bbc.properties.insert("country".to_string(), Value::String("UK".into()));
+        db.mutate_path("series[name == \"Sherlock\"].network", MutationOperation::Set(Value::Node(bbc))).unwrap();
+        db.mutate_path("series[name == \"Elementary\"].network", MutationOperation::Set(Value::Node(episode("CBS")))).unwrap();
+
+        let mut address = HashMap::new();
+        address.insert("city".to_string(), Value::String("London".into()));
+        address.insert("owner".to_string(), Value::Link("BBC".into()));
+        db.set(&[PathPart::field("address")], Value::Map(address)).unwrap();
+
+        let series = get_series(db.select(&"{ series { name network(country == \"UK\") { name } } }".parse().unwrap()));
+        let networks: Vec<Option<&Value>> = series.iter().map(|series| series.properties.get("network")).collect();
+        assert_eq!(networks, [Some(&Value::Null), Some(&Value::Node(episode("BBC")))]);
+
+        let select = |db: &Database, query: &str| db.select(&query.parse().unwrap()).unwrap().remove("address").unwrap();
+        assert_eq!(select(&db, "{ address(city ^= \"Lon\") { city } }"), Value::Map(vec![
+            ("city".to_string(), Value::String("London".into())),
+        ].into_iter().collect()));
+        assert_eq!(select(&db, "{ address(owner.country == \"FR\") { city } }"), Value::Null);
+        assert_eq!(select(&db, "{ address { owner(name == \"CBS\") } }"), Value::Map(vec![
+            ("owner".to_string(), Value::Null),
+        ].into_iter().collect()));
+    }
+
+    #[test]
+    fn aliases_and_computed_fields() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        db.mutate_path("series[name == \"Sherlock\"].network", MutationOperation::Set(Value::Node(episode("BBC")))).unwrap();
+
+        let mut selected = db.select(&"{
+            recent: series(year > 2011) { name }
+            old: series(year <= 2011) { title = concat(name, \" (\", year, \")\") }
+            series {
+                name: year
+                next = year + 1
+                count = length(episodes)
+                network = concat(\"On \", network.name)
+            }
+        }".parse().unwrap()).unwrap();
+
+        let recent = get_list(Ok(selected.clone()), "recent");
+        assert_eq!(names(nodes(recent)), ["Elementary"]);
+
+        let old = nodes(get_list(Ok(selected.clone()), "old"));
+        assert_eq!(old[0].properties.get("title"), Some(&Value::String("Sherlock (2010)".into())));
+
+        let series = get_series(Ok(selected));
+        let fields = |field: &str| series.iter().map(|node| node.properties[field].clone()).collect::<Vec<_>>();
+        assert_eq!(fields("name"), [Value::I64(2012), Value::I64(2010)]);
+        assert_eq!(fields("next"), [Value::I64(2013), Value::I64(2011)]);
+        assert_eq!(fields("count"), [Value::U64(2), Value::U64(2)]);
+        assert_eq!(fields("network"), [Value::Null, Value::String("On BBC".into())]);
+        assert_eq!(series[0].properties.len(), 4);
+
+        let both = Selector::Multi(vec![Selector::field("name"), Selector::field("year")]).alias("both");
+        match db.select(&Selector::traverse("series", both)) {
+            Err(Error::InvalidQuery(_)) => (),
+            result => panic!("Expected an invalid query, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn mutate_path() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        db.mutate_path(
+            "series[name == \"Sherlock\"].episodes[name ^= \"A \"].name",
+            MutationOperation::Set(Value::String("Pink".into())),
+        ).unwrap();
+        db.mutate_path(
+            "series[year < 2011].episodes",
+            MutationOperation::Append(NodeType::Node(episode("The Great Game"))),
+        ).unwrap();
+        assert!(db.mutate_path("series[year <]", MutationOperation::Set(Value::Null)).is_err());
+
+        let mut series = get_series(db.select(&"{ series(name == \"Sherlock\") { episodes { name } } }".parse().unwrap()));
+
+        assert_eq!(names(get_episodes(&mut series[0])), ["Pink", "The Blind Banker", "The Great Game"]);
+    }
 }
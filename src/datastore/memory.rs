@@ -38,6 +38,15 @@ impl DataStore for MemoryDataStore {
         self.values.insert(key.to_string(), value);
         Ok(())
     }
+
+    fn del(&mut self, key: &str) -> Result<()> {
+        debug!("del {}", key);
+
+        self.values.remove(key);
+        self.hashes.remove(key);
+        self.lists.remove(key);
+        Ok(())
+    }
     fn hget(&self, key: &str, properties: Vec<&str>) -> Result<Option<HashMap<String, PrimitiveValue>>> {
         debug!("hget {}, {:?}", key, properties);
 
@@ -93,4 +102,13 @@ impl DataStore for MemoryDataStore {
         list.extend(values.to_owned());
         Ok(())
     }
+
+    fn lrange(&self, key: &str, start: usize, stop: usize) -> Result<Option<Vec<PrimitiveValue>>> {
+        debug!("lrange {}, {}, {}", key, start, stop);
+
+        Ok(self.lists.get(key).map(|list| {
+            let stop = ::std::cmp::min(stop, list.len());
+            list.get(start..stop).map_or_else(Vec::new, <[PrimitiveValue]>::to_vec)
+        }))
+    }
 }
@@ -1,12 +1,17 @@
 pub mod memory;
 
 use std::collections::HashMap;
-use std::io::Result;
+use std::io::{Error, ErrorKind, Result};
 use entities::PrimitiveValue;
 
 pub trait DataStore {
     fn get(&self, key: &str) -> Result<&PrimitiveValue>;
     fn set(&mut self, key: &str, value: PrimitiveValue) -> Result<()>;
+    /// Removes a key of any kind. Stores that can't delete keys don't support
+    /// rewriting lists, as `Database::migrate` does.
+    fn del(&mut self, key: &str) -> Result<()> {
+        Err(Error::new(ErrorKind::Other, format!("del of {} is not supported by this store", key)))
+    }
 
     fn hget(&self, key: &str, properties: Vec<&str>) -> Result<Option<HashMap<String, PrimitiveValue>>>;
     fn hget_all(&self, key: &str) -> Result<Option<HashMap<String, PrimitiveValue>>>;
@@ -15,6 +20,15 @@ pub trait DataStore {
 
     fn lget(&self, key: &str) -> Result<Option<Vec<PrimitiveValue>>>;
     fn lpush(&mut self, key: &str, values: &Vec<PrimitiveValue>) -> Result<()>;
+
+    /// Reads the elements of a list from `start` up to, but not including, `stop`.
+    /// Stores that can read a range without reading the whole list should
+    /// override it.
+    fn lrange(&self, key: &str, start: usize, stop: usize) -> Result<Option<Vec<PrimitiveValue>>> {
+        self.lget(key).map(|list| list.map(|list| {
+            list.into_iter().skip(start).take(stop.saturating_sub(start)).collect()
+        }))
+    }
 }
 
 //abstract class DataStore {
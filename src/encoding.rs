@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use entities::{Error, KakoiResult};
+use node::NodeProperties;
+use value::Value;
+
+/// Initial character of every versioned encoding, chosen so that it does not
+/// collide with raw strings written by other clients or with the legacy prefixes.
+pub const MARKER: char = '\u{1}';
+pub const VERSION: char = '1';
+
+/// Encodes a value as `MARKER VERSION tag payload`.
+///
+/// Numbers, booleans and null can be stored natively as `PrimitiveValue`s so this
+/// is only needed for them when they are nested in a map.
+pub fn encode(value: &Value) -> KakoiResult<String> {
+    encode_tagged(value, encode).map(|tagged| format!("{}{}{}", MARKER, VERSION, tagged))
+}
+
+/// Decodes a value from the current encoding, from the legacy prefixed encoding or,
+/// if it's neither, as a raw string.
+pub fn decode(encoded: &str) -> Value {
+    let mut chars = encoded.chars();
+
+    match chars.next() {
+        Some(MARKER) => match chars.next() {
+            Some(VERSION) => decode_tagged(chars.as_str(), decode),
+            Some(version) => Value::Error(format!("Unsupported encoding version {:?}", version)),
+            None => Value::Error("Missing encoding version".to_string()),
+        },
+        Some(_) => decode_legacy(encoded),
+        None => Value::Null,
+    }
+}
+
+/// Returns true if the string is encoded with the current encoding version and
+/// doesn't need to be migrated.
+pub fn is_current(encoded: &str) -> bool {
+    let mut chars = encoded.chars();
+    chars.next() == Some(MARKER) && chars.next() == Some(VERSION)
+}
+
+/// Decodes the unversioned encoding where a link, list or string is prefixed by a
+/// single tag. Any other string is a raw string.
+pub fn decode_legacy(encoded: &str) -> Value {
+    match encoded.chars().next() {
+        Some(c) if tags::LEGACY.contains(&c) => decode_tagged(encoded, decode_legacy),
+        Some(_) => Value::String(encoded.to_string()),
+        None => Value::Null,
+    }
+}
+
+fn encode_tagged(value: &Value, encode_value: fn(&Value) -> KakoiResult<String>) -> KakoiResult<String> {
+    Ok(match value {
+        &Value::Link(ref node_id) => format!("{}{}", tags::LINK, node_id),
+        &Value::ListLink(ref id) => format!("{}{}", tags::LIST, id),
+        &Value::String(ref string) => format!("{}{}", tags::STRING, string),
+        &Value::Map(ref properties) => try!(encode_map(properties, encode_value)),
+        &Value::I64(num) => format!("{}{}", tags::I64, num),
+        &Value::U64(num) => format!("{}{}", tags::U64, num),
+        &Value::F64(num) => format!("{}{}", tags::F64, num),
+        &Value::Boolean(boolean) => format!("{}{}", tags::BOOLEAN, boolean),
+        &Value::Null => tags::NULL.to_string(),
+        _ => return Err(Error::InvalidValue(format!("Can't encode value {:?} as a string", value))),
+    })
+}
+
+fn decode_tagged(encoded: &str, decode_value: fn(&str) -> Value) -> Value {
+    let mut chars = encoded.chars();
+    let tag = chars.next();
+    let payload = chars.as_str();
+
+    match tag {
+        Some(tags::LINK) => Value::Link(payload.to_string()),
+        Some(tags::LIST) => Value::ListLink(payload.to_string()),
+        Some(tags::STRING) => Value::String(payload.to_string()),
+        Some(tags::MAP) => decode_map(payload, decode_value),
+        Some(tags::I64) => payload.parse().map(Value::I64)
+            .unwrap_or_else(|_| Value::Error(format!("Invalid i64 {}", payload))),
+        Some(tags::U64) => payload.parse().map(Value::U64)
+            .unwrap_or_else(|_| Value::Error(format!("Invalid u64 {}", payload))),
+        Some(tags::F64) => payload.parse().map(Value::F64)
+            .unwrap_or_else(|_| Value::Error(format!("Invalid f64 {}", payload))),
+        Some(tags::BOOLEAN) => payload.parse().map(Value::Boolean)
+            .unwrap_or_else(|_| Value::Error(format!("Invalid boolean {}", payload))),
+        Some(tags::NULL) => Value::Null,
+        Some(c) => Value::Error(format!("Invalid tag {:?}", c)),
+        None => Value::Error("Missing tag".to_string()),
+    }
+}
+
+/// Maps are encoded as length prefixed `key` and `value` pairs so that neither
+/// keys nor values need any escaping.
+fn encode_map(properties: &NodeProperties, encode_value: fn(&Value) -> KakoiResult<String>) -> KakoiResult<String> {
+    let mut keys: Vec<&String> = properties.keys().collect();
+    keys.sort();
+
+    let mut encoded = tags::MAP.to_string();
+    for key in keys {
+        let value = try!(encode_value(&properties[key]));
+        encoded.push_str(&format!("{}:{}{}:{}", key.len(), key, value.len(), value));
+    }
+    Ok(encoded)
+}
+
+fn decode_map(mut encoded: &str, decode_value: fn(&str) -> Value) -> Value {
+    fn split_entry(encoded: &str) -> Option<(&str, &str)> {
+        encoded.find(':').and_then(|colon| {
+            let start = colon + 1;
+            encoded[..colon].parse::<usize>().ok()
+                .map(|len| start + len)
+                .and_then(|end| {
+                    if end <= encoded.len() && encoded.is_char_boundary(end) {
+                        Some((&encoded[start..end], &encoded[end..]))
+                    } else {
+                        None
+                    }
+                })
+        })
+    }
+
+    let mut properties = HashMap::new();
+
+    while !encoded.is_empty() {
+        let entry = split_entry(encoded)
+            .and_then(|(key, rest)| split_entry(rest).map(|(value, rest)| (key, value, rest)));
+
+        match entry {
+            Some((key, value, rest)) => {
+                properties.insert(key.to_owned(), decode_value(value));
+                encoded = rest;
+            },
+            None => return Value::Error(format!("Invalid map entry {}", encoded)),
+        }
+    }
+
+    Value::Map(properties)
+}
+
+mod tags {
+    pub const LINK: char = 'L';
+    pub const STRING: char = 'S';
+    pub const LIST: char = 'l';
+    pub const MAP: char = 'M';
+    pub const I64: char = 'i';
+    pub const U64: char = 'u';
+    pub const F64: char = 'f';
+    pub const BOOLEAN: char = 'b';
+    pub const NULL: char = 'n';
+
+    /// The only tags of the unversioned encoding.
+    pub const LEGACY: [char; 3] = [LINK, STRING, LIST];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use entities::Error;
+    use value::Value;
+
+    #[test]
+    fn round_trip() {
+        let mut map = HashMap::new();
+        map.insert("1:2".to_string(), Value::String("\u{1}1S".into()));
+        map.insert("number".to_string(), Value::F64(-0.5));
+        map.insert("nested".to_string(), Value::Map(HashMap::new()));
+
+        let values = vec![
+            Value::String("".into()),
+            Value::String("Sherlock".into()),
+            Value::Link("id".into()),
+            Value::ListLink("id".into()),
+            Value::I64(-42),
+            Value::U64(42),
+            Value::Boolean(true),
+            Value::Null,
+            Value::Map(map),
+        ];
+
+        for value in values {
+            let encoded = encode(&value).unwrap();
+            assert!(is_current(&encoded), "{:?} is current", encoded);
+            assert_eq!(decode(&encoded), value);
+        }
+    }
+
+    #[test]
+    fn legacy() {
+        assert!(!is_current("SSherlock"));
+        assert_eq!(decode("SSherlock"), Value::String("Sherlock".into()));
+        assert_eq!(decode("Lid"), Value::Link("id".into()));
+        assert_eq!(decode("lid"), Value::ListLink("id".into()));
+    }
+
+    #[test]
+    fn raw() {
+        assert_eq!(decode("Raw string"), Value::String("Raw string".into()));
+
+        for raw in &["Madrid", "index", "union", "false", "bbc", "nobody"] {
+            assert_eq!(decode(raw), Value::String(raw.to_string()));
+        }
+        assert_eq!(decode(""), Value::Null);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(decode("\u{1}2Sfuture"), Value::Error(_)));
+        assert!(matches!(decode("\u{1}1iNaN"), Value::Error(_)));
+        assert!(matches!(decode("\u{1}1M3:key"), Value::Error(_)));
+
+        let mut map = HashMap::new();
+        map.insert("node".to_string(), Value::List(Vec::new()));
+        assert!(matches!(encode(&Value::Map(map)), Err(Error::InvalidValue(_))));
+        assert!(matches!(encode(&Value::Error("error".into())), Err(Error::InvalidValue(_))));
+    }
+}
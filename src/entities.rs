@@ -1,42 +1,123 @@
 use std::io;
+use regex;
+use aggregate::Aggregate;
+use expression::Expression;
 use node::NodeProperties;
+use parser::ParseError;
 use predicate::Predicate;
 use node::Node;
 use value::Value;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum PathPart<'a> {
-    Field(&'a str),
-    FieldFilter(&'a str, Predicate<'a>),
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PathPart {
+    Field(String),
+    FieldFilter(String, Predicate),
 }
 
-pub type Path<'a> = &'a [PathPart<'a>];
+impl PathPart {
+    pub fn field<S: Into<String>>(field: S) -> PathPart {
+        PathPart::Field(field.into())
+    }
+
+    pub fn filter<S: Into<String>>(field: S, filter: Predicate) -> PathPart {
+        PathPart::FieldFilter(field.into(), filter)
+    }
+}
+
+pub type Path<'a> = &'a [PathPart];
 
 #[derive(Debug)]
+// `MultiInMulti` is hidden for compatibility, not to make the enum non-exhaustive.
+#[allow(clippy::manual_non_exhaustive)]
 pub enum Error {
     EmptyPath,
     FieldIsNotTraversable,
-    InvalidValue,
+    InvalidQuery(String),
+    InvalidValue(String),
     Io(io::Error),
+    /// Kept so that existing matches still compile, nested `Multi` selectors are
+    /// now supported.
+    #[doc(hidden)]
+    #[deprecated(note = "nested Multi selectors are supported, so this is never returned")]
     MultiInMulti,
+    Parse(ParseError),
+    Regex(regex::Error),
     Unknown,
 }
 
 pub type KakoiResult<T = ()> = Result<T, Error>;
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum Selector<'a> {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Selector {
     AllFields,
-    Field(&'a str),
-    Multi(Vec<Selector<'a>>),
-    Traverse(&'a str, &'a FilteredSelector<'a>),
+    Field(String),
+    Multi(Vec<Selector>),
+    /// Traverses a link, a map or the elements of a list. The linked node, the map
+    /// and the elements only return the fields the inner selector selects, not the
+    /// fields that are only read by the filter or `order_by`.
+    Traverse(String, Box<FilteredSelector>),
+    /// Selects an aggregate of the elements of a traversed list instead of the
+    /// elements, optionally grouped by the value of a field.
+    Aggregate(Aggregate, Option<String>),
+    /// Selects a field, or a traversal, under another name. The same field can so
+    /// be traversed more than once, with different filters.
+    Alias(String, Box<Selector>),
+    /// Selects the value of an expression over the fields of the node.
+    Computed(String, Expression),
 }
 
-impl<'a> Selector<'a> {
+impl Selector {
+    pub fn field<S: Into<String>>(field: S) -> Selector {
+        Selector::Field(field.into())
+    }
+
+    pub fn traverse<S: Into<String>, F: Into<FilteredSelector>>(field: S, selector: F) -> Selector {
+        Selector::Traverse(field.into(), Box::new(selector.into()))
+    }
+
+    pub fn computed<S: Into<String>>(name: S, expression: Expression) -> Selector {
+        Selector::Computed(name.into(), expression)
+    }
+
+    pub fn alias<S: Into<String>>(self, alias: S) -> Selector {
+        Selector::Alias(alias.into(), Box::new(self))
+    }
+
+    pub fn aggregate(aggregate: Aggregate) -> Selector {
+        Selector::Aggregate(aggregate, None)
+    }
+
+    /// Aggregates each group of elements with the same value of the field, and
+    /// selects the groups as maps with `GROUP_KEY` and `GROUP_VALUE` fields, sorted
+    /// by key.
+    pub fn aggregate_by<S: Into<String>>(aggregate: Aggregate, group_by: S) -> Selector {
+        Selector::Aggregate(aggregate, Some(group_by.into()))
+    }
+
+    /// Only selects the list elements, or the linked node or map, that match the
+    /// predicate when traversed. A node or map that doesn't match is selected as null.
+    pub fn filter(self, filter: Predicate) -> FilteredSelector {
+        FilteredSelector {selector: self, filter: Some(filter), order_by: Vec::new(), page: None}
+    }
+
+    /// Sorts the list elements by the key, when traversed.
+    pub fn order_by(self, key: OrderBy) -> FilteredSelector {
+        FilteredSelector::from(self).order_by(key)
+    }
+
+    /// Only selects a page of the list elements, when traversed.
+    pub fn page(self, page: Page) -> FilteredSelector {
+        FilteredSelector::from(self).page(page)
+    }
+
     pub fn get_fields(&self) -> Option<Vec<&str>> {
         match self {
             &Selector::AllFields => None,
-            &Selector::Field(field) => Some(vec![field]),
+            &Selector::Field(ref field) => Some(vec![field]),
             &Selector::Multi(ref selectors) => {
                 let mut fields = Vec::new();
                 for selector in selectors {
@@ -47,23 +128,52 @@ impl<'a> Selector<'a> {
                 }
                 Some(fields)
             },
-            &Selector::Traverse(field, filter) => {
-                filter.get_fields().map(|mut fields| {
-                    fields.push(field);
-                    fields
-                })
-            }
+            // The traversed selector reads the fields of the linked node.
+            &Selector::Traverse(ref field, _) => Some(vec![field]),
+            &Selector::Alias(_, ref selector) => selector.get_fields(),
+            &Selector::Computed(_, ref expression) => Some(expression.get_fields()),
+            &Selector::Aggregate(ref aggregate, ref group_by) => Some(aggregate
+                .field()
+                .into_iter()
+                .chain(group_by.as_ref().map(String::as_str))
+                .collect()),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct FilteredSelector<'a> {
-    pub selector: Selector<'a>,
-    pub filter: Option<Predicate<'a>>,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FilteredSelector {
+    pub selector: Selector,
+    pub filter: Option<Predicate>,
+    /// The keys the list is sorted by, in order of precedence. Elements with
+    /// equal keys keep their order in the list.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub order_by: Vec<OrderBy>,
+    /// Only selects a page of the list, after filtering and sorting. A paged list
+    /// is returned as a map, see `Page`.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub page: Option<Page>,
 }
 
-impl<'a> FilteredSelector<'a> {
+impl From<Selector> for FilteredSelector {
+    fn from(selector: Selector) -> FilteredSelector {
+        FilteredSelector {selector: selector, filter: None, order_by: Vec::new(), page: None}
+    }
+}
+
+impl FilteredSelector {
+    /// Adds a key to sort by, after the existing keys.
+    pub fn order_by(mut self, key: OrderBy) -> FilteredSelector {
+        self.order_by.push(key);
+        self
+    }
+
+    pub fn page(mut self, page: Page) -> FilteredSelector {
+        self.page = Some(page);
+        self
+    }
+
     pub fn get_fields(&self) -> Option<Vec<&str>> {
         let mut fields = self.selector.get_fields();
 
@@ -74,11 +184,112 @@ impl<'a> FilteredSelector<'a> {
             });
         }
 
-        fields
+        fields.map(|mut f| {
+            f.extend(self.order_by.iter().map(|key| key.field.as_str()));
+            f
+        })
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Order {
+    Ascending,
+    Descending,
+}
+
+/// Where missing and null values are sorted, in either order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+/// A key of a sorted list. The field can be a path, like `network.name`, or `$`
+/// for the elements of a list of values, which are compared with `total_cmp`.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OrderBy {
+    pub field: String,
+    pub order: Order,
+    pub nulls: Nulls,
+}
+
+impl OrderBy {
+    /// Sorts in ascending order, with nulls last.
+    pub fn asc<S: Into<String>>(field: S) -> OrderBy {
+        OrderBy {field: field.into(), order: Order::Ascending, nulls: Nulls::Last}
+    }
+
+    /// Sorts in descending order, with nulls last.
+    pub fn desc<S: Into<String>>(field: S) -> OrderBy {
+        OrderBy {field: field.into(), order: Order::Descending, nulls: Nulls::Last}
+    }
+
+    pub fn nulls(mut self, nulls: Nulls) -> OrderBy {
+        self.nulls = nulls;
+        self
+    }
+}
+
+/// The field of a paged list with the elements of the page.
+pub const PAGE_ELEMENTS: &'static str = "elements";
+/// The field of a paged list that's true when there are more elements after the
+/// page, or before it when paging backwards.
+pub const PAGE_HAS_MORE: &'static str = "has_more";
+/// The fields of a paged list with the cursors of the first and last elements of
+/// the page, or null when they aren't nodes.
+pub const PAGE_START_CURSOR: &'static str = "start_cursor";
+pub const PAGE_END_CURSOR: &'static str = "end_cursor";
+
+/// A page of a list. The cursors are the ids of nodes in the list: the page
+/// starts after `after` and ends before `before`. With only a `before` cursor,
+/// the page is read backwards, so that `offset` and `limit` count from the end.
+/// Links to missing nodes are counted but not returned, so a page of a list that
+/// isn't filtered or sorted can be shorter than `limit` while `has_more` is true.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Page {
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub after: Option<String>,
+    pub before: Option<String>,
+}
+
+impl Page {
+    pub fn new() -> Page {
+        Page::default()
+    }
+
+    pub fn offset(mut self, offset: usize) -> Page {
+        self.offset = offset;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Page {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn after<S: Into<String>>(mut self, cursor: S) -> Page {
+        self.after = Some(cursor.into());
+        self
+    }
+
+    pub fn before<S: Into<String>>(mut self, cursor: S) -> Page {
+        self.before = Some(cursor.into());
+        self
+    }
+
+    /// Whether the page is read backwards from the `before` cursor.
+    pub fn is_backwards(&self) -> bool {
+        self.before.is_some() && self.after.is_none()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PrimitiveValue {
     I64(i64),
     U64(u64),
@@ -94,27 +305,50 @@ impl<'a> From<&'a str> for PrimitiveValue {
     }
 }
 
-impl<'a> From<i64> for PrimitiveValue {
+impl From<String> for PrimitiveValue {
+    fn from(value: String) -> Self {
+        PrimitiveValue::String(value)
+    }
+}
+
+impl From<i64> for PrimitiveValue {
     fn from(value: i64) -> Self {
         PrimitiveValue::I64(value)
     }
 }
 
+impl From<f64> for PrimitiveValue {
+    fn from(value: f64) -> Self {
+        PrimitiveValue::F64(value)
+    }
+}
+
+impl From<bool> for PrimitiveValue {
+    fn from(value: bool) -> Self {
+        PrimitiveValue::Boolean(value)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
-pub struct Mutation<'a> {
-    pub path: Path<'a>,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Mutation {
+    pub path: Vec<PathPart>,
     pub opertaion: MutationOperation,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum NodeType {
     Node(Node),
     Nodes(Vec<Node>),
     Link(String),
     Links(Vec<String>),
+    Value(Value),
+    Values(Vec<Value>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MutationOperation {
     Append(NodeType),
     Set(Value),
@@ -0,0 +1,209 @@
+use std::convert::TryFrom;
+use std::ops;
+use entities::PrimitiveValue;
+use predicate;
+use value::Value;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A value computed from the fields of a node, selected with `Selector::Computed`.
+/// Operations on values of the wrong type, or on null, evaluate to null.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Expression {
+    /// The value of a field, which can be a path like `network.name`.
+    Field(String),
+    Constant(PrimitiveValue),
+    /// The strings, numbers and booleans joined into a string.
+    Concat(Vec<Expression>),
+    /// Integer arithmetic stays integer until it overflows.
+    Add(Box<Expression>, Box<Expression>),
+    Sub(Box<Expression>, Box<Expression>),
+    Mul(Box<Expression>, Box<Expression>),
+    /// Always a `F64`, and null when dividing by zero.
+    Div(Box<Expression>, Box<Expression>),
+    /// The number of elements of a list, or characters of a string, as a `U64`.
+    /// A missing list is empty.
+    Length(String),
+}
+
+use self::Expression::*;
+
+impl Expression {
+    pub fn field<S: Into<String>>(field: S) -> Expression {
+        Field(field.into())
+    }
+
+    pub fn constant<V: Into<PrimitiveValue>>(value: V) -> Expression {
+        Constant(value.into())
+    }
+
+    pub fn length<S: Into<String>>(field: S) -> Expression {
+        Length(field.into())
+    }
+
+    /// The fields the expression reads, including the lists it takes the length of.
+    pub fn get_fields(&self) -> Vec<&str> {
+        match self {
+            &Field(ref field) | &Length(ref field) => vec![field],
+            &Constant(_) => Vec::new(),
+            &Concat(ref expressions) => expressions.iter().flat_map(Expression::get_fields).collect(),
+            &Add(ref a, ref b) | &Sub(ref a, ref b) | &Mul(ref a, ref b) | &Div(ref a, ref b) => {
+                let mut fields = a.get_fields();
+                fields.extend(b.get_fields());
+                fields
+            },
+        }
+    }
+
+    /// Evaluates the expression on a node, or on a value for the `$` field. Links
+    /// and list links are not read, so they evaluate to null.
+    pub fn evaluate(&self, value: &Value) -> Value {
+        match self {
+            &Field(ref field) => predicate::field_value(value, field).clone(),
+            &Constant(ref constant) => match constant {
+                &PrimitiveValue::String(ref string) => Value::String(string.clone()),
+                constant => constant.clone().into(),
+            },
+            &Concat(ref expressions) => {
+                let mut concatenated = String::new();
+                for expression in expressions {
+                    match expression.evaluate(value) {
+                        Value::String(string) => concatenated.push_str(&string),
+                        Value::I64(n) => concatenated.push_str(&n.to_string()),
+                        Value::U64(n) => concatenated.push_str(&n.to_string()),
+                        Value::F64(n) => concatenated.push_str(&n.to_string()),
+                        Value::Boolean(b) => concatenated.push_str(&b.to_string()),
+                        _ => return Value::Null,
+                    }
+                }
+                Value::String(concatenated)
+            },
+            &Add(ref a, ref b) => arithmetic(a.evaluate(value), b.evaluate(value), i64::checked_add, |a, b| a + b),
+            &Sub(ref a, ref b) => arithmetic(a.evaluate(value), b.evaluate(value), i64::checked_sub, |a, b| a - b),
+            &Mul(ref a, ref b) => arithmetic(a.evaluate(value), b.evaluate(value), i64::checked_mul, |a, b| a * b),
+            &Div(ref a, ref b) => match (as_f64(&a.evaluate(value)), as_f64(&b.evaluate(value))) {
+                (Some(a), Some(b)) if b != 0.0 => Value::F64(a / b),
+                _ => Value::Null,
+            },
+            &Length(ref field) => match predicate::field_value(value, field) {
+                &Value::List(ref values) => Value::U64(values.len() as u64),
+                &Value::String(ref string) => Value::U64(string.chars().count() as u64),
+                &Value::Null => Value::U64(0),
+                _ => Value::Null,
+            },
+        }
+    }
+}
+
+/// The arithmetic operators build the `Add`, `Sub`, `Mul` and `Div` expressions.
+impl ops::Add for Expression {
+    type Output = Expression;
+
+    fn add(self, other: Expression) -> Expression {
+        Add(Box::new(self), Box::new(other))
+    }
+}
+
+impl ops::Sub for Expression {
+    type Output = Expression;
+
+    fn sub(self, other: Expression) -> Expression {
+        Sub(Box::new(self), Box::new(other))
+    }
+}
+
+impl ops::Mul for Expression {
+    type Output = Expression;
+
+    fn mul(self, other: Expression) -> Expression {
+        Mul(Box::new(self), Box::new(other))
+    }
+}
+
+impl ops::Div for Expression {
+    type Output = Expression;
+
+    fn div(self, other: Expression) -> Expression {
+        Div(Box::new(self), Box::new(other))
+    }
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    match value {
+        &Value::I64(n) => Some(n),
+        &Value::U64(n) => i64::try_from(n).ok(),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        &Value::I64(n) => Some(n as f64),
+        &Value::U64(n) => Some(n as f64),
+        &Value::F64(n) => Some(n),
+        _ => None,
+    }
+}
+
+fn arithmetic<I, F>(a: Value, b: Value, integer: I, float: F) -> Value
+    where I: Fn(i64, i64) -> Option<i64>, F: Fn(f64, f64) -> f64 {
+    if let (Some(a), Some(b)) = (as_i64(&a), as_i64(&b)) {
+        if let Some(result) = integer(a, b) {
+            return Value::I64(result);
+        }
+    }
+
+    match (as_f64(&a), as_f64(&b)) {
+        (Some(a), Some(b)) => Value::F64(float(a, b)),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use node::Node;
+    use value::Value;
+
+    fn node() -> Value {
+        let mut properties = HashMap::new();
+        properties.insert("name".to_string(), Value::String("Sherlock".into()));
+        properties.insert("year".to_string(), Value::I64(2010));
+        properties.insert("rating".to_string(), Value::F64(9.5));
+        properties.insert("tags".to_string(), Value::List(vec![Value::Null, Value::Boolean(true)]));
+
+        Value::Node(Node {id: "sherlock".to_string(), properties: properties})
+    }
+
+    #[test]
+    fn evaluate() {
+        let node = node();
+
+        assert_eq!(Concat(vec![
+            Expression::field("name"),
+            Expression::constant(" ("),
+            Expression::field("year"),
+            Expression::constant(")"),
+        ]).evaluate(&node), Value::String("Sherlock (2010)".into()));
+        assert_eq!((Expression::field("year") + Expression::constant(1)).evaluate(&node), Value::I64(2011));
+        assert_eq!((Expression::field("rating") * Expression::constant(2)).evaluate(&node), Value::F64(19.0));
+        assert_eq!((Expression::field("year") / Expression::constant(4)).evaluate(&node), Value::F64(502.5));
+        assert_eq!((Expression::constant(i64::max_value()) + Expression::constant(1)).evaluate(&node), Value::F64(i64::max_value() as f64 + 1.0));
+        assert_eq!(Expression::length("tags").evaluate(&node), Value::U64(2));
+        assert_eq!(Expression::length("name").evaluate(&node), Value::U64(8));
+        assert_eq!(Expression::length("episodes").evaluate(&node), Value::U64(0));
+        assert_eq!((Expression::field("$") - Expression::constant(2)).evaluate(&Value::U64(3)), Value::I64(1));
+    }
+
+    #[test]
+    fn null() {
+        let node = node();
+
+        assert_eq!(Concat(vec![Expression::field("name"), Expression::field("missing")]).evaluate(&node), Value::Null);
+        assert_eq!((Expression::field("name") + Expression::constant(1)).evaluate(&node), Value::Null);
+        assert_eq!((Expression::field("year") / Expression::constant(0)).evaluate(&node), Value::Null);
+        assert_eq!(Expression::length("year").evaluate(&node), Value::Null);
+    }
+}
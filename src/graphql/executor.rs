@@ -0,0 +1,676 @@
+use std::collections::HashMap;
+use graphql_parser::query as ast;
+use graphql_parser::query::{Definition, OperationDefinition, Selection, SelectionSet, TypeCondition};
+use database::Database;
+use entities::{Error, FilteredSelector, KakoiResult, MutationOperation, NodeType, PrimitiveValue, Selector};
+use node::{Node, NodeProperties};
+use parser::parse_filter;
+use predicate::Predicate;
+use value::Value;
+use super::schema::{COMPARISON_OPERATORS, FieldType, ObjectType, QUERY, STRING_OPERATORS, Schema};
+
+type Document<'a> = ast::Document<'a, String>;
+type Directive<'a> = ast::Directive<'a, String>;
+type Fragment<'a> = ast::FragmentDefinition<'a, String>;
+type GraphQlValue<'a> = ast::Value<'a, String>;
+
+const TYPENAME: &'static str = "__typename";
+
+fn invalid<T, S: Into<String>>(message: S) -> KakoiResult<T> {
+    Err(Error::InvalidQuery(message.into()))
+}
+
+/// A selected field, with variables, fragments and directives resolved.
+struct Field {
+    /// The alias or the name, which the value is returned as.
+    key: String,
+    name: String,
+    arguments: Vec<(String, Value)>,
+    /// `None` for `__typename`.
+    field_type: Option<FieldType>,
+    fields: Vec<Field>,
+}
+
+struct Context<'a> {
+    schema: &'a Schema,
+    fragments: HashMap<&'a str, &'a Fragment<'a>>,
+    variables: NodeProperties,
+    spreads: Vec<String>,
+}
+
+impl<'a> Context<'a> {
+    fn value(&self, value: &GraphQlValue) -> KakoiResult<Value> {
+        Ok(match value {
+            &ast::Value::Variable(ref name) => self.variables.get(name).cloned().unwrap_or(Value::Null),
+            &ast::Value::Int(ref num) => num.as_i64().map_or(Value::Null, Value::I64),
+            &ast::Value::Float(num) => Value::F64(num),
+            &ast::Value::String(ref string) => Value::String(string.clone()),
+            &ast::Value::Boolean(boolean) => Value::Boolean(boolean),
+            &ast::Value::Null => Value::Null,
+            &ast::Value::Enum(ref name) => Value::String(name.clone()),
+            &ast::Value::List(ref values) => Value::List(try!(values.iter().map(|value| self.value(value)).collect())),
+            &ast::Value::Object(ref object) => {
+                let mut node = Node {id: String::new(), properties: HashMap::new()};
+                for (key, value) in object {
+                    match (key.as_str(), try!(self.value(value))) {
+                        ("id", Value::String(id)) => node.id = id,
+                        (_, value) => { node.properties.insert(key.clone(), value); },
+                    }
+                }
+                Value::Node(node)
+            },
+        })
+    }
+
+    fn is_included(&self, directives: &[Directive]) -> KakoiResult<bool> {
+        for directive in directives {
+            let condition = directive.arguments
+                .iter()
+                .find(|&&(ref name, _)| name == "if")
+                .map_or(Ok(Value::Null), |&(_, ref value)| self.value(value));
+
+            let condition = match try!(condition) {
+                Value::Boolean(condition) => condition,
+                _ => return invalid(format!("The `if` argument of @{} must be a boolean", directive.name)),
+            };
+
+            match directive.name.as_str() {
+                "skip" if condition => return Ok(false),
+                "include" if !condition => return Ok(false),
+                "skip" | "include" => {},
+                name => return invalid(format!("Unknown directive @{}", name)),
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn collect_fields(&mut self, type_name: &str, selection_set: &'a SelectionSet<'a, String>, fields: &mut Vec<Field>) -> KakoiResult {
+        for selection in &selection_set.items {
+            match selection {
+                &Selection::Field(ref field) => {
+                    if !try!(self.is_included(&field.directives)) { continue }
+
+                    let key = field.alias.as_ref().unwrap_or(&field.name).clone();
+                    if field.name == TYPENAME {
+                        fields.push(Field {key: key, name: field.name.clone(), arguments: Vec::new(), field_type: None, fields: Vec::new()});
+                        continue;
+                    }
+
+                    let field_type = match self.schema.get(type_name).and_then(|object| object.get(&field.name)) {
+                        Some(field_type) => field_type.clone(),
+                        None => return invalid(format!("Unknown field `{}` on type `{}`", field.name, type_name)),
+                    };
+
+                    let mut arguments = Vec::new();
+                    for &(ref name, ref value) in &field.arguments {
+                        arguments.push((name.clone(), try!(self.value(value))));
+                    }
+
+                    let mut sub_fields = Vec::new();
+                    match (object_name(&field_type), field.selection_set.items.is_empty()) {
+                        (Some(object), false) => try!(self.collect_fields(object, &field.selection_set, &mut sub_fields)),
+                        (Some(_), true) =>
+                            return invalid(format!("Field `{}` of type `{}` must have a selection of subfields", field.name, field_type)),
+                        (None, false) =>
+                            return invalid(format!("Field `{}` of type `{}` can't have a selection of subfields", field.name, field_type)),
+                        (None, true) => {},
+                    }
+
+                    // Fields with the same key are merged, so that fragments can
+                    // select different subfields of the same field.
+                    if let Some(existing) = fields.iter_mut().find(|existing| existing.key == key) {
+                        if existing.name != field.name || existing.arguments != arguments {
+                            return invalid(format!("Fields with the key `{}` select different fields or arguments", key));
+                        }
+                        existing.fields.extend(sub_fields);
+                        continue;
+                    }
+
+                    fields.push(Field {
+                        key: key,
+                        name: field.name.clone(),
+                        arguments: arguments,
+                        field_type: Some(field_type),
+                        fields: sub_fields,
+                    });
+                },
+                &Selection::FragmentSpread(ref spread) => {
+                    if !try!(self.is_included(&spread.directives)) { continue }
+
+                    let fragment = match self.fragments.get(spread.fragment_name.as_str()) {
+                        Some(fragment) => *fragment,
+                        None => return invalid(format!("Unknown fragment `{}`", spread.fragment_name)),
+                    };
+                    if self.spreads.contains(&spread.fragment_name) {
+                        return invalid(format!("Fragment `{}` spreads itself", spread.fragment_name));
+                    }
+
+                    let TypeCondition::On(ref on) = fragment.type_condition;
+                    if on == type_name {
+                        self.spreads.push(spread.fragment_name.clone());
+                        try!(self.collect_fields(type_name, &fragment.selection_set, fields));
+                        self.spreads.pop();
+                    }
+                },
+                &Selection::InlineFragment(ref fragment) => {
+                    if !try!(self.is_included(&fragment.directives)) { continue }
+
+                    let applies = match fragment.type_condition {
+                        Some(TypeCondition::On(ref on)) => on == type_name,
+                        None => true,
+                    };
+                    if applies {
+                        try!(self.collect_fields(type_name, &fragment.selection_set, fields));
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn object_name(field_type: &FieldType) -> Option<&str> {
+    match field_type {
+        &FieldType::Object(ref name) => Some(name),
+        &FieldType::List(ref of) => object_name(of),
+        _ => None,
+    }
+}
+
+fn primitive(field: &str, value: Value) -> KakoiResult<PrimitiveValue> {
+    Ok(match value {
+        Value::I64(num) => PrimitiveValue::I64(num),
+        Value::U64(num) => PrimitiveValue::U64(num),
+        Value::F64(num) => PrimitiveValue::F64(num),
+        Value::Boolean(boolean) => PrimitiveValue::Boolean(boolean),
+        Value::String(string) => PrimitiveValue::String(string),
+        Value::Null => PrimitiveValue::Null,
+        value => return invalid(format!("Expected a scalar for argument `{}`, got {:?}", field, value)),
+    })
+}
+
+/// Splits an argument like `year_gt` into the field and the operator, which is
+/// empty for equality.
+fn split_argument<'b>(argument: &'b str, object: &ObjectType) -> Option<(&'b str, &'static str)> {
+    let is_property = |field: &str| match object.get(field) {
+        Some(&FieldType::Id) | None => false,
+        Some(_) => true,
+    };
+
+    if is_property(argument) {
+        return Some((argument, ""));
+    }
+
+    COMPARISON_OPERATORS
+        .iter()
+        .chain(STRING_OPERATORS)
+        .filter(|operator| argument.ends_with(*operator))
+        .map(|operator| (&argument[..argument.len() - operator.len()], *operator))
+        .filter(|&(field, _)| field.ends_with('_') && is_property(&field[..field.len() - 1]))
+        .map(|(field, operator)| (&field[..field.len() - 1], operator))
+        .next()
+}
+
+/// Creates the filter of a list from the arguments of the field.
+fn filter(field: &str, arguments: &[(String, Value)], object: Option<&ObjectType>) -> KakoiResult<Option<Predicate>> {
+    let mut predicates = Vec::new();
+
+    for &(ref argument, ref value) in arguments {
+        if argument == "filter" {
+            match value {
+                &Value::String(ref filter) => predicates.push(try!(parse_filter(filter))),
+                &Value::Null => {},
+                _ => return invalid(format!("The filter argument of `{}` must be a string", field)),
+            }
+            continue;
+        }
+
+        let (property, operator) = match object.and_then(|object| split_argument(argument, object)) {
+            Some(split) => split,
+            None => return invalid(format!("Unknown argument `{}` on field `{}`", argument, field)),
+        };
+        let property = property.to_string();
+
+        if operator == "in" {
+            let values = match value.clone() {
+                Value::List(values) => try!(values.into_iter().map(|value| primitive(argument, value)).collect()),
+                value => vec![try!(primitive(argument, value))],
+            };
+            predicates.push(Predicate::In(property, values));
+            continue;
+        }
+
+        let value = try!(primitive(argument, value.clone()));
+        let string = |value: PrimitiveValue| match value {
+            PrimitiveValue::String(string) => Ok(string),
+            _ => invalid(format!("Expected a string for argument `{}`", argument)),
+        };
+
+        predicates.push(match operator {
+            "ne" => Predicate::Neq(property, value),
+            "gt" => Predicate::Gt(property, value),
+            "gte" => Predicate::Gte(property, value),
+            "lt" => Predicate::Lt(property, value),
+            "lte" => Predicate::Lte(property, value),
+            "starts_with" => Predicate::StartsWith(property, try!(string(value))),
+            "ends_with" => Predicate::EndsWith(property, try!(string(value))),
+            "contains" => Predicate::Contains(property, try!(string(value))),
+            _ => Predicate::Eq(property, value),
+        });
+    }
+
+    Ok(match predicates.len() {
+        0 => None,
+        1 => predicates.pop(),
+        _ => Some(Predicate::All(predicates)),
+    })
+}
+
+impl Schema {
+    /// Selects the fields from the node or map. Fields that are selected more than
+    /// once, through aliases or fragments, are selected once with the fields of all
+    /// the selections.
+    fn selector(&self, fields: &[&Field]) -> KakoiResult<Selector> {
+        let mut groups: Vec<(&str, Vec<&Field>)> = Vec::new();
+
+        for field in fields {
+            match field.field_type {
+                None | Some(FieldType::Id) => continue,
+                _ => {},
+            }
+
+            match groups.iter().position(|&(name, _)| name == field.name) {
+                Some(index) => groups[index].1.push(field),
+                None => groups.push((&field.name, vec![field])),
+            }
+        }
+
+        let mut selectors = Vec::new();
+        for (name, group) in groups {
+            if group.iter().any(|field| field.arguments != group[0].arguments) {
+                return invalid(format!("Field `{}` is selected with different arguments", name));
+            }
+
+            let sub_fields: Vec<&Field> = group.iter().flat_map(|field| field.fields.iter()).collect();
+            selectors.push(try!(self.field_selector(group[0], &sub_fields)));
+        }
+
+        Ok(Selector::Multi(selectors))
+    }
+
+    fn field_selector(&self, field: &Field, sub_fields: &[&Field]) -> KakoiResult<Selector> {
+        let field_type = field.field_type.as_ref().unwrap_or(&FieldType::Value);
+        let takes_arguments = match field_type {
+            &FieldType::List(_) => true,
+            _ => false,
+        };
+
+        if !takes_arguments && !field.arguments.is_empty() {
+            return invalid(format!("Field `{}` doesn't take arguments", field.name));
+        }
+
+        let selector = match object_name(field_type) {
+            Some(_) => try!(self.selector(sub_fields)),
+            None if takes_arguments => Selector::AllFields,
+            None => return Ok(Selector::Field(field.name.clone())),
+        };
+        let object = object_name(field_type).and_then(|name| self.get(name));
+
+        Ok(Selector::Traverse(field.name.clone(), Box::new(FilteredSelector {
+            selector: selector,
+            filter: try!(filter(&field.name, &field.arguments, object)),
+            order_by: Vec::new(),
+            page: None,
+        })))
+    }
+
+    fn complete_object(&self, type_name: &str, value: &Value, fields: &[Field]) -> Value {
+        let (id, properties) = match value {
+            &Value::Node(ref node) => (Some(&node.id), &node.properties),
+            &Value::Map(ref properties) => (None, properties),
+            _ => return Value::Null,
+        };
+
+        let mut result = HashMap::new();
+        for field in fields {
+            let value = match field.field_type {
+                None => Value::String(type_name.to_string()),
+                Some(FieldType::Id) if !properties.contains_key(&field.name) =>
+                    id.map_or(Value::Null, |id| Value::String(id.clone())),
+                Some(ref field_type) =>
+                    self.complete(field_type, properties.get(&field.name).unwrap_or(&Value::Null), &field.fields),
+            };
+            result.insert(field.key.clone(), value);
+        }
+
+        Value::Map(result)
+    }
+
+    fn complete(&self, field_type: &FieldType, value: &Value, fields: &[Field]) -> Value {
+        match (field_type, value) {
+            (&FieldType::List(ref of), &Value::List(ref values)) =>
+                Value::List(values.iter().map(|value| self.complete(of, value, fields)).collect()),
+            (&FieldType::Object(ref name), &Value::Node(_)) | (&FieldType::Object(ref name), &Value::Map(_)) =>
+                self.complete_object(name, value, fields),
+            (&FieldType::Object(_), _) | (&FieldType::List(_), _) => Value::Null,
+            (_, &Value::Link(_)) | (_, &Value::ListLink(_)) | (_, &Value::Error(_)) => Value::Null,
+            (_, value) => value.clone(),
+        }
+    }
+
+    fn query(&self, db: &Database, fields: Vec<Field>) -> KakoiResult<NodeProperties> {
+        let mut result = HashMap::new();
+
+        // Every root field is selected separately, so that aliases can select the
+        // same field with different arguments.
+        for field in fields {
+            let value = match field.field_type {
+                None => Value::String(QUERY.to_string()),
+                Some(ref field_type) => {
+                    let sub_fields: Vec<&Field> = field.fields.iter().collect();
+                    let selector = try!(self.field_selector(&field, &sub_fields));
+                    let mut properties = try!(db.select(&selector));
+                    let value = properties.remove(&field.name).unwrap_or(Value::Null);
+
+                    self.complete(field_type, &value, &field.fields)
+                },
+            };
+            result.insert(field.key, value);
+        }
+
+        Ok(result)
+    }
+
+    fn mutation(&self, db: &mut Database, context: &Context, selection_set: &SelectionSet<String>) -> KakoiResult<NodeProperties> {
+        let mut result = HashMap::new();
+
+        for selection in &selection_set.items {
+            let field = match selection {
+                &Selection::Field(ref field) => field,
+                _ => return invalid("Fragments are not supported in mutations"),
+            };
+            if !try!(context.is_included(&field.directives)) { continue }
+            if !field.selection_set.items.is_empty() {
+                return invalid(format!("Mutation `{}` can't have a selection of subfields", field.name));
+            }
+
+            let mut path = None;
+            let mut value = Value::Null;
+            for &(ref name, ref argument) in &field.arguments {
+                match (name.as_str(), try!(context.value(argument))) {
+                    ("path", Value::String(string)) => path = Some(string),
+                    ("value", argument) => value = argument,
+                    (name, _) => return invalid(format!("Invalid argument `{}` on mutation `{}`", name, field.name)),
+                }
+            }
+            let path = match path {
+                Some(path) => path,
+                None => return invalid(format!("Mutation `{}` requires a path", field.name)),
+            };
+
+            let operation = match (field.name.as_str(), value) {
+                ("set", value) => MutationOperation::Set(value),
+                ("merge", Value::Node(node)) => MutationOperation::Merge(node.properties),
+                ("merge", _) => return invalid("Mutation `merge` requires an object value"),
+                ("append", Value::Node(node)) => MutationOperation::Append(NodeType::Node(node)),
+                ("append", Value::List(values)) => {
+                    if values.iter().all(|value| if let &Value::Node(_) = value { true } else { false }) {
+                        MutationOperation::Append(NodeType::Nodes(values.into_iter().filter_map(|value| match value {
+                            Value::Node(node) => Some(node),
+                            _ => None,
+                        }).collect()))
+                    } else {
+                        MutationOperation::Append(NodeType::Values(values))
+                    }
+                },
+                ("append", value) => MutationOperation::Append(NodeType::Value(value)),
+                (name, _) => return invalid(format!("Unknown mutation `{}`", name)),
+            };
+
+            try!(db.mutate_path(&path, operation));
+            result.insert(field.alias.as_ref().unwrap_or(&field.name).clone(), Value::Boolean(true));
+        }
+
+        Ok(result)
+    }
+
+    /// Runs a GraphQL query or mutation against the database and returns the data
+    /// of the response. Objects are returned as `Value::Map`s.
+    ///
+    /// Queries select the fields of the `Query` type. Arguments filter lists: `filter`
+    /// takes a filter expression, see `parser::filter`, and other arguments compare a
+    /// field of the elements, like `name: "Sherlock"` or `year_gt: 2010`.
+    ///
+    /// Mutations are `set`, `merge` and `append`, which take a `path`, see
+    /// `parser::path`, and a `value`. Objects in values are stored as nodes, with the
+    /// id from their `id` field if they have one.
+    pub fn execute(&self, db: &mut Database, document: &str, variables: NodeProperties) -> KakoiResult<NodeProperties> {
+        let document: Document = try!(ast::parse_query(document)
+            .map_err(|err| Error::InvalidQuery(err.to_string())));
+
+        let mut context = Context {
+            schema: self,
+            fragments: HashMap::new(),
+            variables: variables,
+            spreads: Vec::new(),
+        };
+        let mut operations = Vec::new();
+
+        for definition in &document.definitions {
+            match definition {
+                &Definition::Fragment(ref fragment) => { context.fragments.insert(&fragment.name, fragment); },
+                &Definition::Operation(ref operation) => operations.push(operation),
+            }
+        }
+
+        if operations.len() != 1 {
+            return invalid("The document must contain exactly one operation");
+        }
+
+        let (variable_definitions, selection_set) = match operations[0] {
+            &OperationDefinition::SelectionSet(ref selection_set) => (&[][..], selection_set),
+            &OperationDefinition::Query(ref query) => (&query.variable_definitions[..], &query.selection_set),
+            &OperationDefinition::Mutation(ref mutation) => {
+                (&mutation.variable_definitions[..], &mutation.selection_set)
+            },
+            &OperationDefinition::Subscription(_) => return invalid("Subscriptions are not supported"),
+        };
+
+        for definition in variable_definitions {
+            if !context.variables.contains_key(&definition.name) {
+                let value = match definition.default_value {
+                    Some(ref value) => try!(context.value(value)),
+                    None => Value::Null,
+                };
+                context.variables.insert(definition.name.clone(), value);
+            }
+        }
+
+        match operations[0] {
+            &OperationDefinition::Mutation(_) => self.mutation(db, &context, selection_set),
+            _ => {
+                let mut fields = Vec::new();
+                try!(context.collect_fields(QUERY, selection_set, &mut fields));
+                self.query(db, fields)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use database::Database;
+    use datastore::memory::MemoryDataStore;
+    use entities::{Error, PathPart};
+    use graphql::{FieldType, ObjectType, Schema};
+    use node::{Node, NodeProperties};
+    use value::Value;
+
+    fn map(properties: Vec<(&str, Value)>) -> Value {
+        Value::Map(properties.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    fn string(string: &str) -> Value {
+        Value::String(string.to_string())
+    }
+
+    fn serie(id: &str, name: &str, year: i64, episodes: Vec<Value>) -> Value {
+        let mut node = Node {id: id.to_string(), properties: HashMap::new()};
+        node.properties.insert("name".to_string(), string(name));
+        node.properties.insert("year".to_string(), Value::I64(year));
+        node.properties.insert("episodes".to_string(), Value::List(episodes));
+        Value::Node(node)
+    }
+
+    fn episode(id: &str, name: &str) -> Value {
+        let mut node = Node {id: id.to_string(), properties: HashMap::new()};
+        node.properties.insert("name".to_string(), string(name));
+        Value::Node(node)
+    }
+
+    fn create_db<'a>(store: &'a mut MemoryDataStore) -> Database<'a> {
+        let mut db = Database::new(store);
+
+        db.set(&[PathPart::field("series")], Value::List(vec![
+            serie("sherlock", "Sherlock", 2010, vec![
+                episode("pink", "A Study in Pink"),
+                episode("banker", "The Blind Banker"),
+            ]),
+            serie("elementary", "Elementary", 2012, vec![episode("pilot", "Pilot")]),
+        ])).unwrap();
+
+        db
+    }
+
+    fn execute(db: &mut Database, document: &str) -> NodeProperties {
+        Schema::infer(db).unwrap().execute(db, document, HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn infer() {
+        let mut store = MemoryDataStore::new();
+        let db = create_db(&mut store);
+        let schema = Schema::infer(&db).unwrap();
+
+        assert_eq!(schema.get("Series"), Some(&ObjectType::new("Series")
+            .field("id", FieldType::Id)
+            .field("episodes", FieldType::list(FieldType::object("Episodes")))
+            .field("name", FieldType::String)
+            .field("year", FieldType::Int)));
+
+        let sdl = schema.to_string();
+        assert!(sdl.contains("type Query {\n  series(filter: String, name: String, "), "{}", sdl);
+        assert!(sdl.contains("name_starts_with: String, "), "{}", sdl);
+        assert!(sdl.contains("year_in: [Int]): [Series]\n}"), "{}", sdl);
+        assert!(sdl.contains("type Episodes {\n  id: ID\n  name: String\n}"), "{}", sdl);
+    }
+
+    #[test]
+    fn query() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let result = execute(&mut db, "{ series(year_gt: 2010) { __typename id name episodes { title: name } } }");
+
+        assert_eq!(result["series"], Value::List(vec![map(vec![
+            ("__typename", string("Series")),
+            ("id", string("elementary")),
+            ("name", string("Elementary")),
+            ("episodes", Value::List(vec![map(vec![("title", string("Pilot"))])])),
+        ])]));
+    }
+
+    #[test]
+    fn arguments() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+
+        let result = execute(&mut db, r#"
+            query Series($year: Int = 2012) {
+                sherlock: series(name: "Sherlock") { ...names }
+                recent: series(year_gte: $year) { name }
+                short: series(filter: "count(episodes) < 2") { name }
+            }
+
+            fragment names on Series {
+                episodes(name_starts_with: "The") { name }
+                episodes(name_starts_with: "The") { name @skip(if: true) id }
+            }
+        "#);
+
+        assert_eq!(result["sherlock"], Value::List(vec![map(vec![
+            ("episodes", Value::List(vec![map(vec![("name", string("The Blind Banker")), ("id", string("banker"))])])),
+        ])]));
+        assert_eq!(result["recent"], Value::List(vec![map(vec![("name", string("Elementary"))])]));
+        assert_eq!(result["short"], Value::List(vec![map(vec![("name", string("Elementary"))])]));
+    }
+
+    #[test]
+    fn mutation() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+        let schema = Schema::infer(&db).unwrap();
+
+        let mut variables = HashMap::new();
+        variables.insert("name".to_string(), string("The Great Game"));
+
+        let result = schema.execute(&mut db, r#"
+            mutation($name: String) {
+                set(path: "series[name == \"Sherlock\"].year", value: 2011)
+                added: append(path: "series[name == \"Sherlock\"].episodes", value: {id: "game", name: $name})
+            }
+        "#, variables).unwrap();
+
+        assert_eq!(result["set"], Value::Boolean(true));
+        assert_eq!(result["added"], Value::Boolean(true));
+
+        let result = schema.execute(&mut db, r#"{ series(year: 2011) { year episodes(id: "x") { id } } }"#, HashMap::new());
+        assert!(matches!(result, Err(Error::InvalidQuery(_))));
+
+        let result = execute(&mut db, r#"{ series(year: 2011) { year episodes(name_contains: "Game") { id } } }"#);
+        assert_eq!(result["series"], Value::List(vec![map(vec![
+            ("year", Value::I64(2011)),
+            ("episodes", Value::List(vec![map(vec![("id", string("game"))])])),
+        ])]));
+    }
+
+    #[test]
+    fn errors() {
+        let mut store = MemoryDataStore::new();
+        let mut db = create_db(&mut store);
+        let schema = Schema::new()
+            .object(ObjectType::new("Query").field("series", FieldType::list(FieldType::object("Serie"))))
+            .object(ObjectType::new("Serie").field("name", FieldType::String));
+
+        let error = |db: &mut Database, document: &str| match schema.execute(db, document, HashMap::new()) {
+            Err(Error::InvalidQuery(message)) => message,
+            result => panic!("Expected an error for {}, got {:?}", document, result),
+        };
+
+        assert_eq!(error(&mut db, "{ series { year } }"), "Unknown field `year` on type `Serie`");
+        assert_eq!(error(&mut db, "{ series }"), "Field `series` of type `[Serie]` must have a selection of subfields");
+        assert_eq!(error(&mut db, "{ series { name { a } } }"), "Field `name` of type `String` can't have a selection of subfields");
+        assert_eq!(error(&mut db, "{ series(year: 1) { name } }"), "Unknown argument `year` on field `series`");
+        assert_eq!(error(&mut db, "{ series { ...a } } fragment a on Serie { ...a }"), "Fragment `a` spreads itself");
+        assert_eq!(error(&mut db, "mutation { delete(path: \"series\") }"), "Unknown mutation `delete`");
+        assert!(error(&mut db, "{ series {").len() > 0);
+
+        let result = schema.execute(&mut db, "{ series(name: \"Sherlock\") { name } }", HashMap::new()).unwrap();
+        assert_eq!(result["series"], Value::List(vec![map(vec![("name", string("Sherlock"))])]));
+    }
+
+    #[test]
+    fn empty_store() {
+        let mut store = MemoryDataStore::new();
+        let mut db = Database::new(&mut store);
+        let schema = Schema::new()
+            .object(ObjectType::new("Query").field("series", FieldType::list(FieldType::object("Serie"))))
+            .object(ObjectType::new("Serie").field("name", FieldType::String));
+
+        let result = schema.execute(&mut db, "{ series { name } }", HashMap::new()).unwrap();
+        assert_eq!(result["series"], Value::Null);
+    }
+}
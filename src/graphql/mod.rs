@@ -0,0 +1,11 @@
+//! A GraphQL schema and executor on top of `Database`.
+//!
+//! A `Schema` is declared with `ObjectType`s or inferred from the stored nodes
+//! with `Schema::infer`, and renders as SDL with `Display`. `Schema::execute`
+//! runs queries, which become `Selector`s, and mutations, which become
+//! `Mutation`s, in-process.
+
+mod executor;
+mod schema;
+
+pub use self::schema::{COMPARISON_OPERATORS, FieldType, MUTATION, ObjectType, QUERY, STRING_OPERATORS, Schema};
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use database::Database;
+use entities::KakoiResult;
+use node::NodeProperties;
+use value::Value;
+
+/// Name of the root type of queries.
+pub const QUERY: &'static str = "Query";
+/// Name of the root type of mutations.
+pub const MUTATION: &'static str = "Mutation";
+
+/// Suffixes of the arguments that filter a list by comparing a field. An argument
+/// without a suffix filters on equality.
+pub const COMPARISON_OPERATORS: &'static [&'static str] = &["ne", "gt", "gte", "lt", "lte", "in"];
+/// Suffixes of the arguments that filter a list by matching a string field.
+pub const STRING_OPERATORS: &'static [&'static str] = &["starts_with", "ends_with", "contains"];
+
+/// The type of a field. All fields are nullable, as a property can be missing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldType {
+    /// The id of the node, which is not stored as a property.
+    Id,
+    Int,
+    Float,
+    String,
+    Boolean,
+    /// Any value, used for mixed types.
+    Value,
+    Object(String),
+    List(Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn object<S: Into<String>>(name: S) -> FieldType {
+        FieldType::Object(name.into())
+    }
+
+    pub fn list(of: FieldType) -> FieldType {
+        FieldType::List(Box::new(of))
+    }
+
+    pub fn is_scalar(&self) -> bool {
+        match self {
+            &FieldType::Object(_) => false,
+            &FieldType::List(ref of) => of.is_scalar(),
+            _ => true,
+        }
+    }
+
+    fn merge(self, other: FieldType) -> FieldType {
+        match (self, other) {
+            (FieldType::Int, FieldType::Float) | (FieldType::Float, FieldType::Int) => FieldType::Float,
+            (FieldType::List(a), FieldType::List(b)) => FieldType::list(a.merge(*b)),
+            (a, b) => if a == b { a } else { FieldType::Value },
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &FieldType::Id => write!(f, "ID"),
+            &FieldType::Int => write!(f, "Int"),
+            &FieldType::Float => write!(f, "Float"),
+            &FieldType::String => write!(f, "String"),
+            &FieldType::Boolean => write!(f, "Boolean"),
+            &FieldType::Value => write!(f, "Value"),
+            &FieldType::Object(ref name) => write!(f, "{}", name),
+            &FieldType::List(ref of) => write!(f, "[{}]", of),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectType {
+    pub name: String,
+    pub fields: Vec<(String, FieldType)>,
+}
+
+impl ObjectType {
+    pub fn new<S: Into<String>>(name: S) -> ObjectType {
+        ObjectType {name: name.into(), fields: Vec::new()}
+    }
+
+    pub fn field<S: Into<String>>(mut self, name: S, field_type: FieldType) -> ObjectType {
+        self.fields.push((name.into(), field_type));
+        self
+    }
+
+    pub fn get(&self, field: &str) -> Option<&FieldType> {
+        self.fields.iter().find(|&&(ref name, _)| name == field).map(|&(_, ref field_type)| field_type)
+    }
+}
+
+/// The object types of a graph, declared or inferred from the stored nodes, with
+/// the fields of the root node in the `Query` type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schema {
+    types: Vec<ObjectType>,
+}
+
+impl Default for Schema {
+    fn default() -> Schema {
+        Schema::new()
+    }
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema {types: vec![ObjectType::new(QUERY)]}
+    }
+
+    /// Adds a type, or replaces the type with the same name.
+    pub fn object(mut self, object: ObjectType) -> Schema {
+        match self.types.iter().position(|existing| existing.name == object.name) {
+            Some(index) => self.types[index] = object,
+            None => self.types.push(object),
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ObjectType> {
+        self.types.iter().find(|object| object.name == name)
+    }
+
+    /// Infers the types from the nodes reachable from the root. A node or map is
+    /// given a type named after the field it's found in, so `episodes` are of type
+    /// `Episodes`, and fields with values of different types are of type `Value`.
+    pub fn infer(db: &Database) -> KakoiResult<Schema> {
+        let mut inference = Inference {types: BTreeMap::new()};
+        inference.observe_object(QUERY, false, &try!(db.export()));
+
+        let mut schema = Schema::new();
+        for (name, (is_node, fields)) in inference.types {
+            let mut object = ObjectType::new(name);
+            if is_node && !fields.contains_key("id") {
+                object = object.field("id", FieldType::Id);
+            }
+            for (field, field_type) in fields {
+                object = object.field(field, field_type.unwrap_or(FieldType::Value));
+            }
+            schema = schema.object(object);
+        }
+
+        Ok(schema)
+    }
+}
+
+struct Inference {
+    /// Whether the type is a node, with an id, and the types of its fields.
+    types: BTreeMap<String, (bool, BTreeMap<String, Option<FieldType>>)>,
+}
+
+impl Inference {
+    fn observe_object(&mut self, name: &str, is_node: bool, properties: &NodeProperties) {
+        self.types.entry(name.to_string()).or_insert_with(|| (false, BTreeMap::new())).0 |= is_node;
+
+        for (field, value) in properties {
+            let observed = self.observe(field, value);
+            let fields = &mut self.types.get_mut(name).unwrap().1;
+            let field_type = fields.remove(field).and_then(|field_type| field_type);
+
+            fields.insert(field.clone(), match (field_type, observed) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => a.or(b),
+            });
+        }
+    }
+
+    fn observe(&mut self, field: &str, value: &Value) -> Option<FieldType> {
+        match value {
+            &Value::I64(_) | &Value::U64(_) => Some(FieldType::Int),
+            &Value::F64(_) => Some(FieldType::Float),
+            &Value::Boolean(_) => Some(FieldType::Boolean),
+            &Value::String(_) => Some(FieldType::String),
+            &Value::Node(ref node) => {
+                let name = type_name(field);
+                self.observe_object(&name, true, &node.properties);
+                Some(FieldType::Object(name))
+            },
+            &Value::Map(ref properties) => {
+                let name = type_name(field);
+                self.observe_object(&name, false, properties);
+                Some(FieldType::Object(name))
+            },
+            &Value::Link(_) => {
+                let name = type_name(field);
+                self.observe_object(&name, true, &NodeProperties::new());
+                Some(FieldType::Object(name))
+            },
+            &Value::List(ref values) => values
+                .iter()
+                .filter_map(|value| self.observe(field, value))
+                .fold(None, |a: Option<FieldType>, b| Some(a.map_or(b.clone(), |a| a.merge(b))))
+                .map(FieldType::list),
+            &Value::ListLink(_) | &Value::Error(_) | &Value::Null => None,
+        }
+    }
+}
+
+/// `up_next` becomes `UpNext`, and names of the root types get a `Type` suffix.
+fn type_name(field: &str) -> String {
+    let name: String = field
+        .split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars.next().into_iter().flat_map(char::to_uppercase).chain(chars).collect::<Vec<char>>()
+        })
+        .collect();
+
+    if name == QUERY || name == MUTATION { format!("{}Type", name) } else { name }
+}
+
+fn write_arguments(f: &mut fmt::Formatter, schema: &Schema, of: &FieldType) -> fmt::Result {
+    try!(write!(f, "(filter: String"));
+
+    if let &FieldType::Object(ref name) = of {
+        for &(ref field, ref field_type) in schema.get(name).map_or(&[][..], |object| &object.fields[..]) {
+            let operators: Vec<&str> = match field_type {
+                &FieldType::String => COMPARISON_OPERATORS.iter().chain(STRING_OPERATORS).cloned().collect(),
+                &FieldType::Int | &FieldType::Float => COMPARISON_OPERATORS.to_vec(),
+                &FieldType::Boolean => vec!["ne"],
+                _ => continue,
+            };
+
+            try!(write!(f, ", {}: {}", field, field_type));
+            for operator in operators {
+                let argument_type = if operator == "in" { FieldType::list(field_type.clone()) } else { field_type.clone() };
+                try!(write!(f, ", {}_{}: {}", field, operator, argument_type));
+            }
+        }
+    }
+
+    write!(f, ")")
+}
+
+/// Renders the schema in the GraphQL schema definition language.
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "scalar Value"));
+
+        for object in &self.types {
+            try!(writeln!(f, "\ntype {} {{", object.name));
+            for &(ref field, ref field_type) in &object.fields {
+                try!(write!(f, "  {}", field));
+                if let &FieldType::List(ref of) = field_type {
+                    try!(write_arguments(f, self, of));
+                }
+                try!(writeln!(f, ": {}", field_type));
+            }
+            try!(writeln!(f, "}}"));
+        }
+
+        try!(writeln!(f, "\ntype {} {{", MUTATION));
+        try!(writeln!(f, "  set(path: String!, value: Value): Boolean"));
+        try!(writeln!(f, "  merge(path: String!, value: Value!): Boolean"));
+        try!(writeln!(f, "  append(path: String!, value: Value!): Boolean"));
+        writeln!(f, "}}")
+    }
+}
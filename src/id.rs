@@ -0,0 +1,201 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+use encoding;
+use node::NodeProperties;
+use value::Value;
+
+/// Generates ids for lists and for nodes that are stored without an id.
+pub trait IdGenerator {
+    fn node_id(&mut self, properties: &NodeProperties) -> String;
+    fn list_id(&mut self, values: &[Value]) -> String;
+
+    /// Whether equal content gets the same id. Such ids are reused when they are
+    /// already stored, other ids are generated again until they are unused.
+    fn content_addressed(&self) -> bool {
+        false
+    }
+}
+
+/// Random UUIDv4 ids, the default.
+pub struct UuidV4Ids;
+
+impl IdGenerator for UuidV4Ids {
+    fn node_id(&mut self, _: &NodeProperties) -> String {
+        Uuid::new_v4().simple().to_string()
+    }
+
+    fn list_id(&mut self, _: &[Value]) -> String {
+        Uuid::new_v4().simple().to_string()
+    }
+}
+
+/// ULIDs, which sort by the time they were created.
+pub struct UlidIds;
+
+const CROCKFORD: &'static [u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+impl UlidIds {
+    fn generate() -> String {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs() * 1000 + (time.subsec_nanos() / 1000000) as u64)
+            .unwrap_or(0);
+
+        // The version and variant bits of a UUIDv4 are in byte 6 and 8, the others
+        // are random.
+        let uuid = Uuid::new_v4();
+        let bytes = uuid.as_bytes();
+        let random = bytes[0..6].iter().chain(bytes[10..14].iter())
+            .fold(0u128, |random, byte| random << 8 | *byte as u128);
+
+        let ulid = (millis as u128 & 0xFFFF_FFFF_FFFF) << 80 | random;
+
+        (0..26)
+            .rev()
+            .map(|index| CROCKFORD[(ulid >> (index * 5) & 0x1F) as usize] as char)
+            .collect()
+    }
+}
+
+impl IdGenerator for UlidIds {
+    fn node_id(&mut self, _: &NodeProperties) -> String {
+        UlidIds::generate()
+    }
+
+    fn list_id(&mut self, _: &[Value]) -> String {
+        UlidIds::generate()
+    }
+}
+
+/// Monotonically increasing ids, starting at 1. Nodes and lists share the counter,
+/// and ids that are already stored are skipped.
+pub struct CounterIds {
+    next: u64,
+}
+
+impl CounterIds {
+    pub fn new() -> CounterIds {
+        CounterIds::starting_at(1)
+    }
+
+    pub fn starting_at(next: u64) -> CounterIds {
+        CounterIds {next: next}
+    }
+
+    fn generate(&mut self) -> String {
+        let id = self.next.to_string();
+        self.next += 1;
+        id
+    }
+}
+
+impl Default for CounterIds {
+    fn default() -> CounterIds {
+        CounterIds::new()
+    }
+}
+
+impl IdGenerator for CounterIds {
+    fn node_id(&mut self, _: &NodeProperties) -> String {
+        self.generate()
+    }
+
+    fn list_id(&mut self, _: &[Value]) -> String {
+        self.generate()
+    }
+}
+
+/// Ids derived from the content, so equal nodes and equal lists share the same id.
+/// Lists are therefore never changed in place, appending to one stores a copy under
+/// a new id. A merge into a node is visible everywhere the node occurs.
+pub struct ContentHashIds;
+
+/// 64 bit FNV-1a, which unlike the std hashers is stable across releases.
+fn fnv1a(parts: &[&str]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for part in parts {
+        for byte in part.len().to_string().bytes().chain(b":".iter().cloned()).chain(part.bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    format!("{:016x}", hash)
+}
+
+fn encode(value: &Value) -> String {
+    encoding::encode(value).unwrap_or_else(|_| format!("{:?}", value))
+}
+
+impl IdGenerator for ContentHashIds {
+    fn node_id(&mut self, properties: &NodeProperties) -> String {
+        let mut keys: Vec<&String> = properties.keys().collect();
+        keys.sort();
+
+        let encoded: Vec<String> = keys
+            .into_iter()
+            .flat_map(|key| vec![key.clone(), encode(&properties[key])])
+            .collect();
+        let mut parts = vec!["node"];
+        parts.extend(encoded.iter().map(|part| part.as_str()));
+
+        fnv1a(&parts)
+    }
+
+    fn list_id(&mut self, values: &[Value]) -> String {
+        let encoded: Vec<String> = values.iter().map(encode).collect();
+        let mut parts = vec!["list"];
+        parts.extend(encoded.iter().map(|part| part.as_str()));
+
+        fnv1a(&parts)
+    }
+
+    fn content_addressed(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use value::Value;
+
+    fn properties(name: &str) -> HashMap<String, Value> {
+        let mut properties = HashMap::new();
+        properties.insert("name".to_string(), Value::String(name.into()));
+        properties
+    }
+
+    #[test]
+    fn counter() {
+        let mut ids = CounterIds::new();
+
+        assert_eq!(ids.node_id(&properties("Pilot")), "1");
+        assert_eq!(ids.list_id(&[]), "2");
+        assert_eq!(CounterIds::starting_at(42).node_id(&properties("Pilot")), "42");
+    }
+
+    #[test]
+    fn content_hash() {
+        let mut ids = ContentHashIds;
+
+        assert_eq!(ids.node_id(&properties("Pilot")), ids.node_id(&properties("Pilot")));
+        assert!(ids.node_id(&properties("Pilot")) != ids.node_id(&properties("Pilots")));
+        assert!(ids.list_id(&[Value::String("ab".into()), Value::String("c".into())]) !=
+                ids.list_id(&[Value::String("a".into()), Value::String("bc".into())]));
+        assert!(ids.node_id(&HashMap::new()) != ids.list_id(&[]));
+    }
+
+    #[test]
+    fn ulid() {
+        let mut ids = UlidIds;
+        let first = ids.node_id(&HashMap::new());
+        let second = ids.list_id(&[]);
+
+        assert_eq!(first.len(), 26);
+        assert!(first != second);
+        assert!(first.chars().all(|c| CROCKFORD.contains(&(c as u8))));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use serde_json::{self, Map, Number};
+use serde_json::Value as Json;
+use database::Database;
+use entities::{Error, KakoiResult};
+use node::{Node, NodeProperties};
+use value::Value;
+
+/// Key holding the id of a node object.
+pub const ID: &'static str = "$id";
+/// Key of an object referring to a node written elsewhere in the document.
+pub const REF: &'static str = "$ref";
+
+impl<'a> Database<'a> {
+    /// Writes the graph reachable from the root as nested JSON. Nodes are objects
+    /// with their id under `$id` and every later occurrence of a node is written as
+    /// `{"$ref": id}`. Property keys starting with `$` are escaped with another `$`.
+    pub fn export_json(&self) -> KakoiResult<String> {
+        let properties = try!(self.export());
+
+        serde_json::to_string_pretty(&properties_to_json(properties))
+            .map_err(|err| Error::InvalidValue(err.to_string()))
+    }
+
+    /// Reads a document written by `export_json` and sets every property of it on
+    /// the root.
+    pub fn import_json(&mut self, json: &str) -> KakoiResult {
+        let json: Json = try!(serde_json::from_str(json)
+            .map_err(|err| Error::InvalidValue(err.to_string())));
+
+        match json {
+            Json::Object(object) => {
+                let properties = try!(properties_from_json(object));
+                self.import(properties)
+            },
+            json => Err(Error::InvalidValue(format!("Expected a JSON object, got {}", json))),
+        }
+    }
+}
+
+fn escape(key: String) -> String {
+    if key.starts_with('$') { format!("${}", key) } else { key }
+}
+
+fn unescape(key: String) -> KakoiResult<String> {
+    if key.starts_with("$$") {
+        Ok(key[1..].to_string())
+    } else if key.starts_with('$') {
+        Err(Error::InvalidValue(format!("Unexpected reserved key {}", key)))
+    } else {
+        Ok(key)
+    }
+}
+
+fn properties_to_json(properties: NodeProperties) -> Map<String, Json> {
+    properties
+        .into_iter()
+        .map(|(key, value)| (escape(key), to_json(value)))
+        .collect()
+}
+
+pub fn to_json(value: Value) -> Json {
+    match value {
+        Value::I64(num) => Json::Number(num.into()),
+        Value::U64(num) => Json::Number(num.into()),
+        Value::F64(num) => Number::from_f64(num).map_or(Json::Null, Json::Number),
+        Value::Boolean(boolean) => Json::Bool(boolean),
+        Value::String(string) => Json::String(string),
+        Value::Node(node) => {
+            let mut object = properties_to_json(node.properties);
+            object.insert(ID.to_string(), Json::String(node.id));
+            Json::Object(object)
+        },
+        Value::Link(id) => {
+            let mut object = Map::new();
+            object.insert(REF.to_string(), Json::String(id));
+            Json::Object(object)
+        },
+        Value::List(values) => Json::Array(values.into_iter().map(to_json).collect()),
+        Value::Map(properties) => Json::Object(properties_to_json(properties)),
+        Value::ListLink(_) | Value::Error(_) | Value::Null => Json::Null,
+    }
+}
+
+fn properties_from_json(object: Map<String, Json>) -> KakoiResult<NodeProperties> {
+    let mut properties = HashMap::new();
+
+    for (key, json) in object {
+        properties.insert(try!(unescape(key)), try!(from_json(json)));
+    }
+
+    Ok(properties)
+}
+
+pub fn from_json(json: Json) -> KakoiResult<Value> {
+    Ok(match json {
+        Json::Null => Value::Null,
+        Json::Bool(boolean) => Value::Boolean(boolean),
+        Json::Number(num) => match (num.as_i64(), num.as_u64(), num.as_f64()) {
+            (Some(num), _, _) => Value::I64(num),
+            (None, Some(num), _) => Value::U64(num),
+            (None, None, Some(num)) => Value::F64(num),
+            _ => return Err(Error::InvalidValue(format!("Unsupported number {}", num))),
+        },
+        Json::String(string) => Value::String(string),
+        Json::Array(values) => Value::List(try!(values.into_iter().map(from_json).collect())),
+        Json::Object(mut object) => {
+            if let Some(reference) = object.remove(REF) {
+                match (reference, object.is_empty()) {
+                    (Json::String(id), true) => Value::Link(id),
+                    _ => return Err(Error::InvalidValue(format!("Invalid {} object", REF))),
+                }
+            } else if let Some(id) = object.remove(ID) {
+                match id {
+                    Json::String(id) => Value::Node(Node {id: id, properties: try!(properties_from_json(object))}),
+                    id => return Err(Error::InvalidValue(format!("Invalid node id {}", id))),
+                }
+            } else {
+                Value::Map(try!(properties_from_json(object)))
+            }
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use serde_json;
+    use database::Database;
+    use datastore::memory::MemoryDataStore;
+    use entities::PathPart;
+    use node::Node;
+    use value::Value;
+
+    fn node(id: &str, properties: Vec<(&str, Value)>) -> Value {
+        Value::Node(Node {
+            id: id.to_string(),
+            properties: properties.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+        })
+    }
+
+    #[test]
+    fn export_import() {
+        let mut store = MemoryDataStore::new();
+        let json = {
+            let mut db = Database::new(&mut store);
+
+            let mut address = HashMap::new();
+            address.insert("$city".to_string(), Value::String("London".into()));
+
+            db.set(&[PathPart::Field("series".into())], Value::List(vec![
+                node("sherlock", vec![
+                    ("name", Value::String("Sherlock".into())),
+                    ("year", Value::I64(2010)),
+                    ("rating", Value::F64(9.1)),
+                    ("network", node("bbc", vec![("name", Value::String("BBC".into()))])),
+                    ("next", Value::Link("elementary".into())),
+                    ("address", Value::Map(address)),
+                ]),
+                node("elementary", vec![
+                    ("name", Value::String("Elementary".into())),
+                    ("tags", Value::List(vec![Value::Boolean(true), Value::Null, Value::U64(u64::max_value())])),
+                    ("network", Value::Link("bbc".into())),
+                    ("next", Value::Link("sherlock".into())),
+                ]),
+            ])).unwrap();
+
+            db.export_json().unwrap()
+        };
+
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["series"][0]["$id"], "sherlock");
+        assert_eq!(parsed["series"][0]["network"]["name"], "BBC");
+        assert_eq!(parsed["series"][0]["address"]["$$city"], "London");
+        assert_eq!(parsed["series"][0]["next"]["next"]["$ref"], "sherlock");
+        assert_eq!(parsed["series"][1]["$ref"], "elementary");
+
+        let mut other_store = MemoryDataStore::new();
+        let mut db = Database::new(&mut other_store);
+        db.import_json(&json).unwrap();
+
+        assert_eq!(db.export_json().unwrap(), json);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut store = MemoryDataStore::new();
+        let mut db = Database::new(&mut store);
+
+        assert!(db.import_json("[]").is_err());
+        assert!(db.import_json("{\"a\": {\"$ref\": 1}}").is_err());
+        assert!(db.import_json("{\"$a\": 1}").is_err());
+        assert!(db.import_json("{").is_err());
+    }
+}
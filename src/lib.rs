@@ -1,12 +1,33 @@
 #[macro_use]
 extern crate log;
 extern crate env_logger;
+extern crate regex;
 extern crate uuid;
+#[cfg(feature = "derive")]
+extern crate kakoi_derive;
+#[cfg(feature = "graphql")]
+extern crate graphql_parser;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(any(feature = "json", all(test, feature = "serde")))]
+extern crate serde_json;
 
+pub mod aggregate;
 pub mod datastore;
 pub mod database;
+pub mod encoding;
 pub mod entities;
+pub mod expression;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod id;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod keys;
 pub mod node;
+pub mod parser;
 pub mod predicate;
 pub mod value;
+
+#[cfg(feature = "derive")]
+pub use kakoi_derive::KakoiNode;
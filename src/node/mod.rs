@@ -1,12 +1,17 @@
 pub mod hashnode;
+pub mod typed;
 
 use std::collections::HashMap;
-use entities::KakoiResult;
+use std::convert::TryFrom;
+use entities::{Error, KakoiResult};
 use value::Value;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 pub type NodeProperties = HashMap<String, Value>;
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Node {
     pub id: String,
     pub properties: NodeProperties,
@@ -25,11 +30,13 @@ impl Node {
     }
 }
 
-impl<T> Into<HashMap<String, T>> for Node where T: From<Value> {
-    fn into(self) -> HashMap<String, T> {
-        self.properties
+impl<T> TryFrom<Node> for HashMap<String, T> where T: TryFrom<Value, Error = Error> {
+    type Error = Error;
+
+    fn try_from(node: Node) -> KakoiResult<Self> {
+        node.properties
             .into_iter()
-            .map(|(field, value)| (field, value.into()))
+            .map(|(field, value)| T::try_from(value).map(|value| (field, value)))
             .collect()
     }
 }
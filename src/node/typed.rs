@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use entities::{Error, KakoiResult};
+use node::Node;
+use value::Value;
+
+/// A Rust type that maps to a node, usually implemented with `#[derive(KakoiNode)]`.
+pub trait KakoiNode: Sized {
+    fn into_node(self) -> Node;
+    fn from_node(node: Node) -> KakoiResult<Self>;
+}
+
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> KakoiResult<Self>;
+}
+
+fn invalid<T>(expected: &str, value: Value) -> KakoiResult<T> {
+    Err(Error::InvalidValue(format!("Expected {}, got {:?}", expected, value)))
+}
+
+/// Reads a property for a derived `KakoiNode`, treating a missing property as null.
+pub fn take_property<T: FromValue>(node: &mut Node, property: &str) -> KakoiResult<T> {
+    T::from_value(node.properties.remove(property).unwrap_or(Value::Null))
+        .map_err(|err| match err {
+            Error::InvalidValue(message) => Error::InvalidValue(format!("{}: {}", property, message)),
+            err => err,
+        })
+}
+
+/// Reads a derived `KakoiNode` from a selected node or an inline map.
+pub fn node_from_value<T: KakoiNode>(value: Value) -> KakoiResult<T> {
+    match value {
+        Value::Node(node) => T::from_node(node),
+        Value::Map(properties) => T::from_node(Node {id: String::new(), properties: properties}),
+        value => invalid("a node", value),
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::String(string) => Ok(string),
+            value => invalid("a string", value),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::Boolean(boolean) => Ok(boolean),
+            value => invalid("a boolean", value),
+        }
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::I64(self)
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::I64(num) => Ok(num),
+            Value::U64(num) if num <= i64::max_value() as u64 => Ok(num as i64),
+            value => invalid("an i64", value),
+        }
+    }
+}
+
+impl IntoValue for i32 {
+    fn into_value(self) -> Value {
+        Value::I64(self as i64)
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::I64(num) if num as i32 as i64 == num => Ok(num as i32),
+            Value::U64(num) if num <= i32::max_value() as u64 => Ok(num as i32),
+            value => invalid("an i32", value),
+        }
+    }
+}
+
+impl IntoValue for u64 {
+    fn into_value(self) -> Value {
+        Value::U64(self)
+    }
+}
+
+impl FromValue for u64 {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::U64(num) => Ok(num),
+            Value::I64(num) if num >= 0 => Ok(num as u64),
+            value => invalid("a u64", value),
+        }
+    }
+}
+
+impl IntoValue for u32 {
+    fn into_value(self) -> Value {
+        Value::U64(self as u64)
+    }
+}
+
+impl FromValue for u32 {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::U64(num) if num <= u32::max_value() as u64 => Ok(num as u32),
+            Value::I64(num) if num >= 0 && num <= u32::max_value() as i64 => Ok(num as u32),
+            value => invalid("a u32", value),
+        }
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::F64(self)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::F64(num) => Ok(num),
+            Value::I64(num) => Ok(num as f64),
+            Value::U64(num) => Ok(num as f64),
+            value => invalid("an f64", value),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Null, IntoValue::into_value)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::List(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::List(values) => values.into_iter().map(T::from_value).collect(),
+            value => invalid("a list", value),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Value {
+        Value::Map(self.into_iter().map(|(key, value)| (key, value.into_value())).collect())
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: Value) -> KakoiResult<Self> {
+        match value {
+            Value::Map(properties) => properties
+                .into_iter()
+                .map(|(key, value)| T::from_value(value).map(|value| (key, value)))
+                .collect(),
+            value => invalid("a map", value),
+        }
+    }
+}
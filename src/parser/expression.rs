@@ -0,0 +1,225 @@
+//! Computed field expressions, such as `concat(name, " (", year, ")")`.
+//!
+//! | Syntax                           | Expression                    |
+//! |----------------------------------|-------------------------------|
+//! | `a`, `network.name`, `$`         | `Field`                       |
+//! | `"x"`, `1`, `2.5`, `true`, `null` | `Constant`                   |
+//! | `a + b`, `a - b`, `a * b`, `a / b` | `Add`, `Sub`, `Mul`, `Div`  |
+//! | `concat(a, " ", b)`              | `Concat`                      |
+//! | `length(a)`                      | `Length`                      |
+//! | `(a)`                            | `a`                           |
+//!
+//! `*` and `/` bind tighter than `+` and `-`, and all of them are left associative.
+
+use std::fmt;
+use std::str::FromStr;
+use entities::{Error, KakoiResult};
+use expression::Expression;
+use super::{Field, Literal, ParseError, Parser};
+use super::lexer::Token;
+
+/// Parses a computed field expression.
+pub fn parse_expression(input: &str) -> KakoiResult<Expression> {
+    let mut parser = try!(Parser::new(input));
+    let expression = try!(parser.expression());
+    try!(parser.end());
+
+    Ok(expression)
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(input: &str) -> KakoiResult<Expression> {
+        parse_expression(input)
+    }
+}
+
+impl Parser {
+    pub fn expression(&mut self) -> KakoiResult<Expression> {
+        let mut expression = try!(self.term());
+
+        loop {
+            if self.eat("+") {
+                expression = expression + try!(self.term());
+            } else if self.eat("-") {
+                expression = expression - try!(self.term());
+            } else if let Token::Number(number) = self.peek().clone() {
+                // The lexer reads `a -1` as a field followed by a negative number.
+                if !number.starts_with('-') {
+                    return Ok(expression);
+                }
+                let constant = try!(self.number(&number[1..]));
+                self.next();
+                expression = expression - try!(self.factor(Expression::Constant(constant)));
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    fn term(&mut self) -> KakoiResult<Expression> {
+        let operand = try!(self.operand());
+        self.factor(operand)
+    }
+
+    fn factor(&mut self, mut expression: Expression) -> KakoiResult<Expression> {
+        loop {
+            if self.eat("*") {
+                expression = expression * try!(self.operand());
+            } else if self.eat("/") {
+                expression = expression / try!(self.operand());
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    fn operand(&mut self) -> KakoiResult<Expression> {
+        if self.eat("(") {
+            let expression = try!(self.expression());
+            try!(self.expect(")"));
+            return Ok(expression);
+        }
+
+        let function = match (self.peek(), self.peek_at(1)) {
+            (&Token::Identifier(ref name), &Token::Punct("(")) => Some(name.clone()),
+            (&Token::Identifier(ref name), _) if name != "true" && name != "false" && name != "null" => {
+                let field = name.clone();
+                self.next();
+                return Ok(Expression::Field(field));
+            },
+            _ => None,
+        };
+
+        let name = match function {
+            Some(name) => name,
+            None => return self.value().map(Expression::Constant).or_else(|_| self.unexpected("an expression")),
+        };
+        let column = self.column();
+        self.next();
+        self.next();
+
+        let expression = match name.as_str() {
+            "concat" => {
+                let mut expressions = vec![try!(self.expression())];
+                while self.eat(",") {
+                    expressions.push(try!(self.expression()));
+                }
+                Expression::Concat(expressions)
+            },
+            "length" => Expression::Length(try!(self.identifier("a field"))),
+            _ => return Err(Error::Parse(ParseError::new(column, format!("Unknown function `{}`", name)))),
+        };
+        try!(self.expect(")"));
+
+        Ok(expression)
+    }
+}
+
+/// How tightly an expression binds, for the parentheses it needs as an operand.
+fn precedence(expression: &Expression) -> u8 {
+    match expression {
+        &Expression::Add(..) | &Expression::Sub(..) => 1,
+        &Expression::Mul(..) | &Expression::Div(..) => 2,
+        _ => 3,
+    }
+}
+
+fn write_binary(f: &mut fmt::Formatter, operator: &str, a: &Expression, b: &Expression, level: u8) -> fmt::Result {
+    // Operators are left associative, so a right operand at the same level needs
+    // parentheses to read back the same way.
+    if precedence(a) < level {
+        try!(write!(f, "({})", a));
+    } else {
+        try!(write!(f, "{}", a));
+    }
+    try!(write!(f, " {} ", operator));
+    if precedence(b) <= level { write!(f, "({})", b) } else { write!(f, "{}", b) }
+}
+
+/// Renders the expression in the syntax that `parse_expression` reads back.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Expression::Field(ref field) => write!(f, "{}", Field(field)),
+            &Expression::Constant(ref constant) => write!(f, "{}", Literal(constant)),
+            &Expression::Concat(ref expressions) => {
+                try!(write!(f, "concat("));
+                for (index, expression) in expressions.iter().enumerate() {
+                    try!(write!(f, "{}{}", if index > 0 { ", " } else { "" }, expression));
+                }
+                write!(f, ")")
+            },
+            &Expression::Add(ref a, ref b) => write_binary(f, "+", a, b, 1),
+            &Expression::Sub(ref a, ref b) => write_binary(f, "-", a, b, 1),
+            &Expression::Mul(ref a, ref b) => write_binary(f, "*", a, b, 2),
+            &Expression::Div(ref a, ref b) => write_binary(f, "/", a, b, 2),
+            &Expression::Length(ref field) => write!(f, "length({})", Field(field)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entities::PrimitiveValue;
+
+    fn error(input: &str) -> ParseError {
+        match parse_expression(input) {
+            Err(Error::Parse(error)) => error,
+            result => panic!("Expected a parse error for {}, got {:?}", input, result),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_expression("concat(name, \" (\", year, \")\")").unwrap(),
+            Expression::Concat(vec![
+                Expression::field("name"),
+                Expression::constant(" ("),
+                Expression::field("year"),
+                Expression::constant(")"),
+            ])
+        );
+        assert_eq!(
+            parse_expression("a + b * 2 - (c - 1) / length(episodes)").unwrap(),
+            Expression::field("a") + Expression::field("b") * Expression::constant(2) -
+                (Expression::field("c") - Expression::constant(1)) / Expression::length("episodes")
+        );
+        assert_eq!(
+            parse_expression("year -1").unwrap(),
+            Expression::field("year") - Expression::constant(1)
+        );
+        assert_eq!(
+            parse_expression("`odd field` * -2.5").unwrap(),
+            Expression::field("odd field") * Expression::Constant(PrimitiveValue::F64(-2.5))
+        );
+        assert_eq!(parse_expression("null").unwrap(), Expression::Constant(PrimitiveValue::Null));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("a +"), ParseError::new(4, "Expected an expression, found end of input"));
+        assert_eq!(error("upper(name)"), ParseError::new(1, "Unknown function `upper`"));
+        assert_eq!(error("length(\"x\")"), ParseError::new(8, "Expected a field, found \"x\""));
+        assert_eq!(error("concat(a b)"), ParseError::new(10, "Expected `)`, found `b`"));
+    }
+
+    #[test]
+    fn display() {
+        let expressions = [
+            "concat(name, \" (\", year, \")\")",
+            "a + b * 2 - (c - 1) / length(episodes)",
+            "(a + b) * (c / 2.0)",
+            "a - (b + c)",
+            "`odd field` * -1",
+            "true",
+        ];
+
+        for expression in expressions.iter() {
+            assert_eq!(&parse_expression(expression).unwrap().to_string(), expression);
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Filter expressions, such as
+//! `year >= 2010 && (name == "Sherlock" || name ^= "Ele")`.
+//!
+//! | Syntax                           | Predicate                     |
+//! |----------------------------------|-------------------------------|
+//! | `a == 1`, `a = 1`, `a != 1`      | `Eq`, `Neq`                   |
+//! | `a < 1`, `a <= 1`, `a > 1`, `a >= 1` | `Lt`, `Lte`, `Gt`, `Gte`  |
+//! | `a in [1, 2]`                    | `In`                          |
+//! | `a between 1 and 2`              | `Between`                     |
+//! | `a ^= "x"`, `a $= "x"`, `a *= "x"` | `StartsWith`, `EndsWith`, `Contains` |
+//! | `a ~= "x"`                       | `EqIgnoreCase`                |
+//! | `a =~ "^x"`                      | `Matches`                     |
+//! | `exists(a)`, `is_null(a)`        | `Exists`, `IsNull`            |
+//! | `any(a, p)`, `all(a, p)`, `none(a, p)` | `AnyElement`, `AllElements`, `NoElement` |
+//! | `count(a) > 1`, `count(a, $ > 1)` | `Count`                      |
+//! | `p && q`, `p \|\| q`, `!p`, `(p)` | `All`, `Any`, `Not`          |
+//! | `true`, `false`                  | `All([])`, `Any([])`          |
+//!
+//! `!` binds tighter than `&&`, which binds tighter than `||`. Fields can be paths,
+//! like `network.name`, and fields that aren't identifiers can be quoted with
+//! backticks. Values are strings, numbers, `true`, `false` and `null`.
+
+use std::fmt;
+use std::str::FromStr;
+use entities::{Error, KakoiResult};
+use predicate::{ELEMENT, Pattern, Predicate};
+use predicate::Predicate::*;
+use super::{Field, Literal, ParseError, Parser, write_string};
+use super::lexer::Token;
+
+/// Parses a filter expression into a predicate.
+pub fn parse_filter(input: &str) -> KakoiResult<Predicate> {
+    let mut parser = try!(Parser::new(input));
+    let predicate = try!(parser.filter());
+    try!(parser.end());
+
+    Ok(predicate)
+}
+
+impl FromStr for Predicate {
+    type Err = Error;
+
+    fn from_str(input: &str) -> KakoiResult<Predicate> {
+        parse_filter(input)
+    }
+}
+
+impl Parser {
+    pub fn filter(&mut self) -> KakoiResult<Predicate> {
+        let mut predicates = vec![try!(self.conjunction())];
+
+        while self.eat("||") {
+            predicates.push(try!(self.conjunction()));
+        }
+
+        Ok(if predicates.len() == 1 { predicates.remove(0) } else { Any(predicates) })
+    }
+
+    fn conjunction(&mut self) -> KakoiResult<Predicate> {
+        let mut predicates = vec![try!(self.negation())];
+
+        while self.eat("&&") {
+            predicates.push(try!(self.negation()));
+        }
+
+        Ok(if predicates.len() == 1 { predicates.remove(0) } else { All(predicates) })
+    }
+
+    fn negation(&mut self) -> KakoiResult<Predicate> {
+        if self.eat("!") {
+            self.negation().map(|predicate| !predicate)
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> KakoiResult<Predicate> {
+        if self.eat("(") {
+            let predicate = try!(self.filter());
+            try!(self.expect(")"));
+            return Ok(predicate);
+        }
+
+        let is_call = self.peek_at(1) == &Token::Punct("(");
+        let is_constant = match self.peek_at(1) {
+            &Token::End | &Token::Punct(")") | &Token::Punct("]") |
+            &Token::Punct("&&") | &Token::Punct("||") | &Token::Punct(",") => true,
+            _ => false,
+        };
+
+        match self.peek().clone() {
+            Token::Identifier(ref name) if is_call => self.call(name),
+            Token::Identifier(ref name) if is_constant && name == "true" => {
+                self.next();
+                Ok(All(Vec::new()))
+            },
+            Token::Identifier(ref name) if is_constant && name == "false" => {
+                self.next();
+                Ok(Any(Vec::new()))
+            },
+            Token::Identifier(field) => {
+                self.next();
+                self.comparison(field)
+            },
+            _ => self.unexpected("a field"),
+        }
+    }
+
+    fn call(&mut self, name: &str) -> KakoiResult<Predicate> {
+        let column = self.column();
+        self.next();
+        try!(self.expect("("));
+        let field = try!(self.identifier("a field"));
+
+        let predicate = match name {
+            "exists" => Exists(field),
+            "is_null" => IsNull(field),
+            "count" if self.is(")") => {
+                self.next();
+                let predicate = try!(self.comparison(ELEMENT.to_string()));
+                return Ok(Count(field, Box::new(predicate)));
+            },
+            "any" | "all" | "none" | "count" => {
+                try!(self.expect(","));
+                let predicate = Box::new(try!(self.filter()));
+                match name {
+                    "any" => AnyElement(field, predicate),
+                    "all" => AllElements(field, predicate),
+                    "none" => NoElement(field, predicate),
+                    _ => Count(field, predicate),
+                }
+            },
+            _ => return Err(Error::Parse(ParseError::new(column, format!("Unknown function `{}`", name)))),
+        };
+
+        try!(self.expect(")"));
+        Ok(predicate)
+    }
+
+    fn comparison(&mut self, field: String) -> KakoiResult<Predicate> {
+        let operator = self.peek().clone();
+
+        if let Token::Identifier(ref keyword) = operator {
+            if keyword == "in" {
+                self.next();
+                try!(self.expect("["));
+                let mut values = Vec::new();
+
+                if !self.is("]") {
+                    values.push(try!(self.value()));
+                    while self.eat(",") {
+                        values.push(try!(self.value()));
+                    }
+                }
+
+                try!(self.expect("]"));
+                return Ok(In(field, values));
+            }
+
+            if keyword == "between" {
+                self.next();
+                let low = try!(self.value());
+                try!(self.expect_keyword("and"));
+                let high = try!(self.value());
+                return Ok(Between(field, low, high));
+            }
+        }
+
+        let operator = match operator {
+            Token::Punct(operator) => operator,
+            _ => return self.unexpected("an operator"),
+        };
+
+        match operator {
+            "==" | "=" | "!=" | "<" | "<=" | ">" | ">=" => {
+                self.next();
+                let value = try!(self.value());
+
+                Ok(match operator {
+                    "!=" => Neq(field, value),
+                    "<" => Lt(field, value),
+                    "<=" => Lte(field, value),
+                    ">" => Gt(field, value),
+                    ">=" => Gte(field, value),
+                    _ => Eq(field, value),
+                })
+            },
+            "^=" | "$=" | "*=" | "~=" => {
+                self.next();
+                let string = try!(self.string());
+
+                Ok(match operator {
+                    "^=" => StartsWith(field, string),
+                    "$=" => EndsWith(field, string),
+                    "*=" => Contains(field, string),
+                    _ => EqIgnoreCase(field, string),
+                })
+            },
+            "=~" => {
+                self.next();
+                let column = self.column();
+                let pattern = try!(self.string());
+
+                Pattern::new(&pattern)
+                    .map(|pattern| Matches(field, pattern))
+                    .map_err(|err| match err {
+                        Error::Regex(err) => Error::Parse(ParseError::new(column, format!("Invalid regex: {}", err))),
+                        err => err,
+                    })
+            },
+            _ => self.unexpected("an operator"),
+        }
+    }
+}
+
+fn write_joined(f: &mut fmt::Formatter, predicates: &[Predicate], separator: &str) -> fmt::Result {
+    for (index, predicate) in predicates.iter().enumerate() {
+        if index > 0 {
+            try!(write!(f, " {} ", separator));
+        }
+
+        try!(match predicate {
+            &Any(ref predicates) if predicates.len() > 1 && separator == "&&" => write!(f, "({})", predicate),
+            _ => write!(f, "{}", predicate),
+        });
+    }
+
+    Ok(())
+}
+
+/// Renders the predicate as a filter expression that `parse_filter` reads back.
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Eq(ref field, ref value) => write!(f, "{} == {}", Field(field), Literal(value)),
+            &Neq(ref field, ref value) => write!(f, "{} != {}", Field(field), Literal(value)),
+            &Lt(ref field, ref value) => write!(f, "{} < {}", Field(field), Literal(value)),
+            &Lte(ref field, ref value) => write!(f, "{} <= {}", Field(field), Literal(value)),
+            &Gt(ref field, ref value) => write!(f, "{} > {}", Field(field), Literal(value)),
+            &Gte(ref field, ref value) => write!(f, "{} >= {}", Field(field), Literal(value)),
+            &All(ref predicates) if predicates.is_empty() => write!(f, "true"),
+            &Any(ref predicates) if predicates.is_empty() => write!(f, "false"),
+            &All(ref predicates) => write_joined(f, predicates, "&&"),
+            &Any(ref predicates) => write_joined(f, predicates, "||"),
+            &Exists(ref field) => write!(f, "exists({})", Field(field)),
+            &IsNull(ref field) => write!(f, "is_null({})", Field(field)),
+            &Not(ref predicate) => match **predicate {
+                Exists(_) | IsNull(_) | Not(_) |
+                AnyElement(..) | AllElements(..) | NoElement(..) => write!(f, "!{}", predicate),
+                _ => write!(f, "!({})", predicate),
+            },
+            &In(ref field, ref values) => {
+                try!(write!(f, "{} in [", Field(field)));
+                for (index, value) in values.iter().enumerate() {
+                    try!(write!(f, "{}{}", if index > 0 { ", " } else { "" }, Literal(value)));
+                }
+                write!(f, "]")
+            },
+            &Between(ref field, ref low, ref high) =>
+                write!(f, "{} between {} and {}", Field(field), Literal(low), Literal(high)),
+            &StartsWith(ref field, ref string) => {
+                try!(write!(f, "{} ^= ", Field(field)));
+                write_string(f, string)
+            },
+            &EndsWith(ref field, ref string) => {
+                try!(write!(f, "{} $= ", Field(field)));
+                write_string(f, string)
+            },
+            &Contains(ref field, ref string) => {
+                try!(write!(f, "{} *= ", Field(field)));
+                write_string(f, string)
+            },
+            &EqIgnoreCase(ref field, ref string) => {
+                try!(write!(f, "{} ~= ", Field(field)));
+                write_string(f, string)
+            },
+            &Matches(ref field, ref pattern) => {
+                try!(write!(f, "{} =~ ", Field(field)));
+                write_string(f, pattern.as_str())
+            },
+            &AnyElement(ref field, ref predicate) => write!(f, "any({}, {})", Field(field), predicate),
+            &AllElements(ref field, ref predicate) => write!(f, "all({}, {})", Field(field), predicate),
+            &NoElement(ref field, ref predicate) => write!(f, "none({}, {})", Field(field), predicate),
+            &Count(ref field, ref predicate) => match **predicate {
+                Eq(ref element, _) | Neq(ref element, _) |
+                Lt(ref element, _) | Lte(ref element, _) |
+                Gt(ref element, _) | Gte(ref element, _) if element == ELEMENT => {
+                    let comparison = predicate.to_string();
+                    write!(f, "count({}){}", Field(field), &comparison[ELEMENT.len()..])
+                },
+                _ => write!(f, "count({}, {})", Field(field), predicate),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entities::PrimitiveValue;
+
+    fn error(input: &str) -> ParseError {
+        match parse_filter(input) {
+            Err(Error::Parse(error)) => error,
+            result => panic!("Expected a parse error for {}, got {:?}", input, result),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_filter("year >= 2010 && (name == \"Sherlock\" || name ^= \"Ele\")").unwrap(),
+            All(vec![
+                Gte("year".into(), 2010.into()),
+                Any(vec![
+                    Eq("name".into(), "Sherlock".into()),
+                    StartsWith("name".into(), "Ele".into()),
+                ]),
+            ])
+        );
+        assert_eq!(
+            parse_filter("a = 1 || b < -2.5 && !c != null").unwrap(),
+            Any(vec![
+                Eq("a".into(), 1.into()),
+                All(vec![Lt("b".into(), PrimitiveValue::F64(-2.5)), !Neq("c".into(), PrimitiveValue::Null)]),
+            ])
+        );
+        assert_eq!(
+            "network.name in [\"BBC\", \"CBS\"] && `odd field` between 1 and 18446744073709551615".parse::<Predicate>().unwrap(),
+            All(vec![
+                In("network.name".into(), vec!["BBC".into(), "CBS".into()]),
+                Between("odd field".into(), 1.into(), PrimitiveValue::U64(u64::max_value())),
+            ])
+        );
+        assert_eq!(
+            parse_filter("count(episodes) > 10 && any(episodes, name ~= \"pilot\") && !exists(ended)").unwrap(),
+            All(vec![
+                Count("episodes".into(), Box::new(Gt(ELEMENT.into(), 10.into()))),
+                AnyElement("episodes".into(), Box::new(EqIgnoreCase("name".into(), "pilot".into()))),
+                !Exists("ended".into()),
+            ])
+        );
+        assert_eq!(parse_filter("name =~ \"^S\"").unwrap(), Predicate::regex("name", "^S").unwrap());
+        assert_eq!(parse_filter("true").unwrap(), All(vec![]));
+        assert_eq!(parse_filter("true == false").unwrap(), Eq("true".into(), false.into()));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("year >= "), ParseError::new(9, "Expected a value, found end of input"));
+        assert_eq!(error("year 2010"), ParseError::new(6, "Expected an operator, found `2010`"));
+        assert_eq!(error("(a == 1"), ParseError::new(8, "Expected `)`, found end of input"));
+        assert_eq!(error("a == 1 b"), ParseError::new(8, "Expected end of input, found `b`"));
+        assert_eq!(error("a ^= 1"), ParseError::new(6, "Expected a string, found `1`"));
+        assert_eq!(error("size(a) > 1"), ParseError::new(1, "Unknown function `size`"));
+        assert_eq!(error("a == \"b"), ParseError::new(6, "Unterminated string"));
+        assert_eq!(error("a =~ \"(\"").column, 6);
+        assert_eq!(error("a == 99999999999999999999").message, "Number out of range");
+    }
+
+    #[test]
+    fn display() {
+        let filters = [
+            "year >= 2010 && (name == \"Sherlock\" || name ^= \"Ele\")",
+            "a == 1 || b < -2.5 && !(c != null)",
+            "network.name in [\"B\\\"BC\", 1.0, true] && `odd field` between 1 and 2",
+            "count(episodes) > 10 && count(episodes, $ > 1 && $ < 5) && none(episodes, name $= \"x\")",
+            "!exists(a) && is_null(b) && c *= \"x\" && d ~= \"y\" && e =~ \"^\\\\d\" && !(true || false)",
+        ];
+
+        for filter in filters.iter() {
+            let predicate = parse_filter(filter).unwrap();
+            assert_eq!(&predicate.to_string(), filter);
+            assert_eq!(parse_filter(&predicate.to_string()).unwrap(), predicate);
+        }
+    }
+}
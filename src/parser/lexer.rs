@@ -0,0 +1,181 @@
+use std::fmt;
+use super::ParseError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Identifier(String),
+    String(String),
+    Number(String),
+    Punct(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Token::Identifier(ref identifier) => write!(f, "`{}`", identifier),
+            &Token::String(ref string) => write!(f, "{:?}", string),
+            &Token::Number(ref number) => write!(f, "`{}`", number),
+            &Token::Punct(punct) => write!(f, "`{}`", punct),
+            &Token::End => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Longest first, so that `<=` is not read as `<` followed by `=`.
+const PUNCTS: &'static [&'static str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "^=", "$=", "*=", "~=", "=~",
+    "!", "<", ">", "=", "(", ")", "[", "]", "{", "}", ",", ".", ":",
+    "+", "-", "*", "/", "@",
+];
+
+/// `$` may only start an identifier, so that `name$="x"` reads as `name $= "x"`.
+pub fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+}
+
+pub fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Splits the input into tokens, each with the 1-based column it starts at. The
+/// last token is always `Token::End`.
+///
+/// Identifiers may contain dots, so a field path like `network.name` is a single
+/// token. Identifiers that are not valid this way can be quoted with backticks.
+pub fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let column = index + 1;
+        let next = chars.get(index + 1).cloned();
+
+        if c.is_whitespace() {
+            index += 1;
+        } else if c == '"' {
+            let mut string = String::new();
+            index += 1;
+
+            loop {
+                match chars.get(index).cloned() {
+                    None => return Err(ParseError::new(column, "Unterminated string")),
+                    Some('"') => break,
+                    Some('\\') => {
+                        string.push(match chars.get(index + 1).cloned() {
+                            Some('"') => '"',
+                            Some('\\') => '\\',
+                            Some('n') => '\n',
+                            Some('r') => '\r',
+                            Some('t') => '\t',
+                            _ => return Err(ParseError::new(index + 1, "Invalid escape sequence")),
+                        });
+                        index += 2;
+                    },
+                    Some(c) => {
+                        string.push(c);
+                        index += 1;
+                    },
+                }
+            }
+
+            index += 1;
+            tokens.push((Token::String(string), column));
+        } else if c == '`' {
+            let start = index + 1;
+            index = start;
+
+            while index < chars.len() && chars[index] != '`' {
+                index += 1;
+            }
+            if index == chars.len() {
+                return Err(ParseError::new(column, "Unterminated quoted identifier"));
+            }
+
+            tokens.push((Token::Identifier(chars[start..index].iter().collect()), column));
+            index += 1;
+        } else if c.is_ascii_digit() || (c == '-' && next.map_or(false, |c| c.is_ascii_digit())) {
+            let start = index;
+            index += 1;
+
+            while index < chars.len() && chars[index].is_ascii_digit() { index += 1 }
+            if chars.get(index) == Some(&'.') && chars.get(index + 1).map_or(false, |c| c.is_ascii_digit()) {
+                index += 1;
+                while index < chars.len() && chars[index].is_ascii_digit() { index += 1 }
+            }
+            if chars.get(index) == Some(&'e') || chars.get(index) == Some(&'E') {
+                index += 1;
+                if chars.get(index) == Some(&'-') || chars.get(index) == Some(&'+') { index += 1 }
+                while index < chars.len() && chars[index].is_ascii_digit() { index += 1 }
+            }
+
+            tokens.push((Token::Number(chars[start..index].iter().collect()), column));
+        } else if is_identifier_start(c) && !(c == '$' && next == Some('=') && chars.get(index + 2) != Some(&'=')) {
+            let start = index;
+            index += 1;
+
+            while index < chars.len() {
+                if is_identifier_char(chars[index]) {
+                    index += 1;
+                } else if chars[index] == '.' && chars.get(index + 1).map_or(false, |&c| is_identifier_start(c)) {
+                    index += 2;
+                } else {
+                    break;
+                }
+            }
+
+            tokens.push((Token::Identifier(chars[start..index].iter().collect()), column));
+        } else {
+            let punct = PUNCTS.iter().find(|punct| {
+                punct.chars().enumerate().all(|(offset, p)| chars.get(index + offset) == Some(&p))
+            });
+
+            match punct {
+                Some(punct) => {
+                    index += punct.len();
+                    tokens.push((Token::Punct(punct), column));
+                },
+                None => return Err(ParseError::new(column, format!("Unexpected character `{}`", c))),
+            }
+        }
+    }
+
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::Token::*;
+
+    fn tokens(input: &str) -> Vec<Token> {
+        tokenize(input).unwrap().into_iter().map(|(token, _)| token).collect()
+    }
+
+    #[test]
+    fn tokens_and_columns() {
+        assert_eq!(tokenize("year>=2010").unwrap(), vec![
+            (Identifier("year".into()), 1),
+            (Punct(">="), 5),
+            (Number("2010".into()), 7),
+            (End, 11),
+        ]);
+        assert_eq!(tokens("network.name == \"B\\\"BC\""), vec![
+            Identifier("network.name".into()), Punct("=="), String("B\"BC".into()), End,
+        ]);
+        assert_eq!(tokens("$ $= -1.5e3 `a b`"), vec![
+            Identifier("$".into()), Punct("$="), Number("-1.5e3".into()), Identifier("a b".into()), End,
+        ]);
+        assert_eq!(tokens("name$=\"x\""), vec![Identifier("name".into()), Punct("$="), String("x".into()), End]);
+        assert_eq!(tokens("a].b"), vec![Identifier("a".into()), Punct("]"), Punct("."), Identifier("b".into()), End]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(tokenize("name == \"Sher").unwrap_err(), ParseError::new(9, "Unterminated string"));
+        assert_eq!(tokenize("a # b").unwrap_err(), ParseError::new(3, "Unexpected character `#`"));
+    }
+}
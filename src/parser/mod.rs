@@ -0,0 +1,213 @@
+use std::fmt;
+use entities::{Error, KakoiResult, PrimitiveValue};
+use predicate::SEPARATOR;
+use self::lexer::{is_identifier_char, is_identifier_start, Token};
+
+pub mod expression;
+pub mod filter;
+mod lexer;
+pub mod path;
+pub mod query;
+
+pub use self::expression::parse_expression;
+pub use self::filter::parse_filter;
+pub use self::path::parse_path;
+pub use self::query::parse_query;
+
+/// An error in a parsed text, with the 1-based column where it was found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new<S: Into<String>>(column: usize, message: S) -> ParseError {
+        ParseError {column: column, message: message.into()}
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+/// Renders a field, quoted with backticks if it isn't an identifier.
+struct Field<'a>(&'a str);
+
+impl<'a> fmt::Display for Field<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let is_identifier = self.0.split(SEPARATOR).all(|segment| {
+            segment.chars().next().map_or(false, is_identifier_start) &&
+                segment.chars().skip(1).all(is_identifier_char)
+        });
+
+        if is_identifier { write!(f, "{}", self.0) } else { write!(f, "`{}`", self.0) }
+    }
+}
+
+/// Renders a string as a quoted literal that the lexer reads back.
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    try!(write!(f, "\""));
+    for c in string.chars() {
+        try!(match c {
+            '"' => write!(f, "\\\""),
+            '\\' => write!(f, "\\\\"),
+            '\n' => write!(f, "\\n"),
+            '\r' => write!(f, "\\r"),
+            '\t' => write!(f, "\\t"),
+            c => write!(f, "{}", c),
+        });
+    }
+    write!(f, "\"")
+}
+
+/// Renders a value as a literal that `Parser::value` reads back.
+struct Literal<'a>(&'a PrimitiveValue);
+
+impl<'a> fmt::Display for Literal<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            &PrimitiveValue::I64(num) => write!(f, "{}", num),
+            &PrimitiveValue::U64(num) => write!(f, "{}", num),
+            &PrimitiveValue::F64(num) => write!(f, "{:?}", num),
+            &PrimitiveValue::Boolean(boolean) => write!(f, "{}", boolean),
+            &PrimitiveValue::String(ref string) => write_string(f, string),
+            &PrimitiveValue::Null => write!(f, "null"),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> KakoiResult<Parser> {
+        lexer::tokenize(input)
+            .map(|tokens| Parser {tokens: tokens, position: 0})
+            .map_err(Error::Parse)
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        let position = ::std::cmp::min(self.position + offset, self.tokens.len() - 1);
+        &self.tokens[position].0
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.position].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if self.position < self.tokens.len() - 1 {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error<T, S: Into<String>>(&self, message: S) -> KakoiResult<T> {
+        Err(Error::Parse(ParseError::new(self.column(), message)))
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> KakoiResult<T> {
+        self.error(format!("Expected {}, found {}", expected, self.peek()))
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        match self.peek() {
+            &Token::Punct(p) => p == punct,
+            _ => false,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            &Token::Identifier(ref identifier) => identifier == keyword,
+            _ => false,
+        }
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if self.is(punct) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> KakoiResult {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{}`", punct))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> KakoiResult {
+        if self.is_keyword(keyword) {
+            self.next();
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{}`", keyword))
+        }
+    }
+
+    fn identifier(&mut self, expected: &str) -> KakoiResult<String> {
+        match self.peek().clone() {
+            Token::Identifier(identifier) => {
+                self.next();
+                Ok(identifier)
+            },
+            _ => self.unexpected(expected),
+        }
+    }
+
+    fn string(&mut self) -> KakoiResult<String> {
+        match self.peek().clone() {
+            Token::String(string) => {
+                self.next();
+                Ok(string)
+            },
+            _ => self.unexpected("a string"),
+        }
+    }
+
+    fn value(&mut self) -> KakoiResult<PrimitiveValue> {
+        let value = match self.peek() {
+            &Token::String(ref string) => PrimitiveValue::String(string.clone()),
+            &Token::Number(ref number) => try!(self.number(number)),
+            &Token::Identifier(ref identifier) if identifier == "true" => PrimitiveValue::Boolean(true),
+            &Token::Identifier(ref identifier) if identifier == "false" => PrimitiveValue::Boolean(false),
+            &Token::Identifier(ref identifier) if identifier == "null" => PrimitiveValue::Null,
+            _ => return self.unexpected("a value"),
+        };
+
+        self.next();
+        Ok(value)
+    }
+
+    fn number(&self, number: &str) -> KakoiResult<PrimitiveValue> {
+        if number.contains(|c| c == '.' || c == 'e' || c == 'E') {
+            return number.parse().map(PrimitiveValue::F64).or_else(|_| self.error("Invalid number"));
+        }
+
+        number.parse().map(PrimitiveValue::I64)
+            .or_else(|_| number.parse().map(PrimitiveValue::U64))
+            .or_else(|_| self.error("Number out of range"))
+    }
+
+    fn end(&mut self) -> KakoiResult {
+        match self.peek() {
+            &Token::End => Ok(()),
+            _ => self.unexpected("end of input"),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Paths, such as `series[name == "Sherlock"].episodes.name`.
+//!
+//! A path is a list of fields separated by dots. A field followed by a filter in
+//! brackets becomes a `PathPart::FieldFilter`. Filters use the syntax of
+//! `parse_filter`.
+
+use entities::{KakoiResult, PathPart};
+use predicate::SEPARATOR;
+use super::Parser;
+use super::lexer::Token;
+
+/// Parses a path into the parts of a mutation path.
+pub fn parse_path(input: &str) -> KakoiResult<Vec<PathPart>> {
+    let mut parser = try!(Parser::new(input));
+    let mut path = Vec::new();
+
+    loop {
+        let fields = match parser.peek().clone() {
+            Token::Identifier(fields) => {
+                parser.next();
+                fields
+            },
+            _ => return parser.unexpected("a field"),
+        };
+
+        // The lexer reads `episodes.name` as one identifier.
+        path.extend(fields.split(SEPARATOR).map(PathPart::field));
+
+        if parser.eat("[") {
+            let filter = try!(parser.filter());
+            try!(parser.expect("]"));
+
+            if let Some(PathPart::Field(field)) = path.pop() {
+                path.push(PathPart::FieldFilter(field, filter));
+            }
+        }
+
+        if !parser.eat(".") {
+            break;
+        }
+    }
+
+    try!(parser.end());
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entities::{Error, PrimitiveValue};
+    use parser::ParseError;
+    use predicate::Predicate;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_path("series[name=\"Sherlock\"].episodes.name").unwrap(), vec![
+            PathPart::filter("series", Predicate::Eq("name".into(), "Sherlock".into())),
+            PathPart::field("episodes"),
+            PathPart::field("name"),
+        ]);
+        assert_eq!(parse_path("a.b[x > 1 && y.z == null].c[true]").unwrap(), vec![
+            PathPart::field("a"),
+            PathPart::filter("b", Predicate::All(vec![
+                Predicate::Gt("x".into(), 1.into()),
+                Predicate::Eq("y.z".into(), PrimitiveValue::Null),
+            ])),
+            PathPart::filter("c", Predicate::All(vec![])),
+        ]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |input| match parse_path(input) {
+            Err(Error::Parse(error)) => error,
+            result => panic!("Expected a parse error for {}, got {:?}", input, result),
+        };
+
+        assert_eq!(error(""), ParseError::new(1, "Expected a field, found end of input"));
+        assert_eq!(error("series[name]"), ParseError::new(12, "Expected an operator, found `]`"));
+        assert_eq!(error("series[name == 1"), ParseError::new(17, "Expected `]`, found end of input"));
+        assert_eq!(error("series."), ParseError::new(8, "Expected a field, found end of input"));
+        assert_eq!(error("series name"), ParseError::new(8, "Expected end of input, found `name`"));
+    }
+}
@@ -0,0 +1,441 @@
+//! Queries, such as `{ series(year > 2010) { name episodes { name } } }`.
+//!
+//! A query is a block of selections. A selection is `*` for all fields, a field
+//! name, or a field name followed by a filter in parentheses and/or a block,
+//! which traverses the field. A block with more than one selection becomes a
+//! `Selector::Multi`. Filters use the syntax of `parse_filter` and a filtered
+//! field without a block selects all fields of the matching elements.
+//!
+//! A traversed list can be sorted with `order by` after the filter, such as
+//! `{ series order by year desc, name asc nulls first { name } }`. Keys are
+//! ascending with nulls last by default. A page of it can then be selected with
+//! `limit 10`, `offset 20`, `after "id"` and `before "id"`, in any order, which
+//! returns the list as a map described by `Page`.
+//!
+//! A list can be aggregated instead, with `@count()`, `@sum(field)`, `@min(field)`,
+//! `@max(field)`, `@avg(field)` or `@distinct(field)` as the only selection of its
+//! block, optionally followed by `by field`, such as `{ series { @count() by year } }`.
+//!
+//! A field can be selected under another name with `alias: selection`, such as
+//! `{ recent: series(year > 2010) { name } }`, so that the same field can be
+//! selected more than once. `name = expression` selects a field computed with the
+//! syntax of `parse_expression`, such as `{ title = concat(name, " (", year, ")") }`.
+
+use std::fmt;
+use std::str::FromStr;
+use aggregate::Aggregate;
+use entities::{Error, FilteredSelector, KakoiResult, Nulls, Order, OrderBy, Page, Selector};
+use super::{Field, ParseError, Parser, write_string};
+use super::lexer::Token;
+
+/// Parses a query into a selector.
+pub fn parse_query(input: &str) -> KakoiResult<Selector> {
+    let mut parser = try!(Parser::new(input));
+    let selector = try!(parser.block());
+    try!(parser.end());
+
+    Ok(selector)
+}
+
+impl FromStr for Selector {
+    type Err = Error;
+
+    fn from_str(input: &str) -> KakoiResult<Selector> {
+        parse_query(input)
+    }
+}
+
+impl Parser {
+    fn block(&mut self) -> KakoiResult<Selector> {
+        try!(self.expect("{"));
+        let mut selectors = Vec::new();
+
+        while !self.eat("}") {
+            selectors.push(try!(self.selection()));
+            self.eat(",");
+        }
+
+        Ok(if selectors.len() == 1 { selectors.remove(0) } else { Selector::Multi(selectors) })
+    }
+
+    fn selection(&mut self) -> KakoiResult<Selector> {
+        if self.eat("*") {
+            return Ok(Selector::AllFields);
+        }
+        if self.eat("@") {
+            return self.aggregate();
+        }
+
+        let field = match self.peek().clone() {
+            Token::Identifier(field) => {
+                self.next();
+                field
+            },
+            _ => return self.unexpected("a field or `}`"),
+        };
+
+        if self.eat(":") {
+            return match self.peek() {
+                &Token::Identifier(_) => self.selection().map(|selector| selector.alias(field)),
+                _ => self.unexpected("a field"),
+            };
+        }
+        if self.eat("=") {
+            return self.expression().map(|expression| Selector::Computed(field, expression));
+        }
+
+        let filter = if self.eat("(") {
+            let filter = try!(self.filter());
+            try!(self.expect(")"));
+            Some(filter)
+        } else {
+            None
+        };
+
+        let order_by = try!(self.order_by());
+        let page = try!(self.page());
+
+        let selector = if self.is("{") {
+            try!(self.block())
+        } else if filter.is_some() || !order_by.is_empty() || page.is_some() {
+            Selector::AllFields
+        } else {
+            return Ok(Selector::Field(field));
+        };
+
+        Ok(Selector::Traverse(field, Box::new(FilteredSelector {
+            selector: selector,
+            filter: filter,
+            order_by: order_by,
+            page: page,
+        })))
+    }
+
+    fn page(&mut self) -> KakoiResult<Option<Page>> {
+        let mut page: Option<Page> = None;
+
+        // Like `order`, these are only keywords when followed by their argument.
+        loop {
+            let keyword = match (self.peek(), self.peek_at(1)) {
+                (&Token::Identifier(ref keyword), &Token::Number(_)) if keyword == "offset" || keyword == "limit" =>
+                    keyword.clone(),
+                (&Token::Identifier(ref keyword), &Token::String(_)) if keyword == "after" || keyword == "before" =>
+                    keyword.clone(),
+                _ => return Ok(page),
+            };
+            self.next();
+
+            let current = page.take().unwrap_or_else(Page::new);
+            page = Some(match keyword.as_str() {
+                "offset" => current.offset(try!(self.count())),
+                "limit" => current.limit(try!(self.count())),
+                "after" => current.after(try!(self.string())),
+                _ => current.before(try!(self.string())),
+            });
+        }
+    }
+
+    fn count(&mut self) -> KakoiResult<usize> {
+        let count = match self.peek() {
+            &Token::Number(ref number) => number.parse().ok(),
+            _ => None,
+        };
+
+        match count {
+            Some(count) => {
+                self.next();
+                Ok(count)
+            },
+            None => self.unexpected("a count"),
+        }
+    }
+
+    fn aggregate(&mut self) -> KakoiResult<Selector> {
+        let column = self.column();
+        let name = try!(self.identifier("an aggregate"));
+        try!(self.expect("("));
+
+        let aggregate = if name == "count" {
+            Aggregate::Count
+        } else {
+            let field = try!(self.identifier("a field"));
+            match name.as_str() {
+                "sum" => Aggregate::Sum(field),
+                "min" => Aggregate::Min(field),
+                "max" => Aggregate::Max(field),
+                "avg" => Aggregate::Avg(field),
+                "distinct" => Aggregate::Distinct(field),
+                _ => return Err(Error::Parse(ParseError::new(column, format!("Unknown aggregate `{}`", name)))),
+            }
+        };
+        try!(self.expect(")"));
+
+        let group_by = if self.is_keyword("by") {
+            self.next();
+            Some(try!(self.identifier("a field")))
+        } else {
+            None
+        };
+
+        Ok(Selector::Aggregate(aggregate, group_by))
+    }
+
+    fn order_by(&mut self) -> KakoiResult<Vec<OrderBy>> {
+        // `order` is only a keyword when followed by `by`, so that it can still be
+        // selected as a field.
+        if !self.is_keyword("order") || self.peek_at(1) != &Token::Identifier("by".to_string()) {
+            return Ok(Vec::new());
+        }
+        self.next();
+        self.next();
+
+        let mut keys = vec![try!(self.order_key())];
+        while self.eat(",") {
+            keys.push(try!(self.order_key()));
+        }
+
+        Ok(keys)
+    }
+
+    fn order_key(&mut self) -> KakoiResult<OrderBy> {
+        let mut key = OrderBy::asc(try!(self.identifier("a field")));
+
+        if self.is_keyword("asc") || self.is_keyword("desc") {
+            if self.is_keyword("desc") {
+                key.order = Order::Descending;
+            }
+            self.next();
+        }
+
+        if self.is_keyword("nulls") {
+            self.next();
+            if self.is_keyword("first") {
+                key.nulls = Nulls::First;
+            } else if !self.is_keyword("last") {
+                return self.unexpected("`first` or `last`");
+            }
+            self.next();
+        }
+
+        Ok(key)
+    }
+}
+
+fn write_selections(f: &mut fmt::Formatter, selector: &Selector) -> fmt::Result {
+    match selector {
+        &Selector::AllFields => write!(f, "*"),
+        &Selector::Field(ref field) => write!(f, "{}", Field(field)),
+        &Selector::Aggregate(ref aggregate, ref group_by) => {
+            try!(write!(f, "@{}(", aggregate));
+            if let Some(field) = aggregate.field() {
+                try!(write!(f, "{}", Field(field)));
+            }
+            try!(write!(f, ")"));
+            match group_by {
+                &Some(ref field) => write!(f, " by {}", Field(field)),
+                &None => Ok(()),
+            }
+        },
+        &Selector::Alias(ref alias, ref selector) => {
+            try!(write!(f, "{}: ", Field(alias)));
+            write_selections(f, selector)
+        },
+        &Selector::Computed(ref name, ref expression) => write!(f, "{} = {}", Field(name), expression),
+        &Selector::Multi(ref selectors) => {
+            for (index, selector) in selectors.iter().enumerate() {
+                if index > 0 {
+                    try!(write!(f, " "));
+                }
+                try!(write_selections(f, selector));
+            }
+            Ok(())
+        },
+        &Selector::Traverse(ref field, ref selector) => {
+            try!(write!(f, "{}", Field(field)));
+            if let Some(ref filter) = selector.filter {
+                try!(write!(f, "({})", filter));
+            }
+            for (index, key) in selector.order_by.iter().enumerate() {
+                try!(write!(f, "{}", if index == 0 { " order by " } else { ", " }));
+                try!(write_order_key(f, key));
+            }
+
+            if let Some(ref page) = selector.page {
+                try!(write_page(f, page));
+            }
+
+            match selector.selector {
+                Selector::AllFields if selector.filter.is_some() || !selector.order_by.is_empty() || selector.page.is_some() =>
+                    Ok(()),
+                ref selector => write_block(f, selector),
+            }
+        },
+    }
+}
+
+fn write_order_key(f: &mut fmt::Formatter, key: &OrderBy) -> fmt::Result {
+    try!(write!(f, "{}", Field(&key.field)));
+    if key.order == Order::Descending {
+        try!(write!(f, " desc"));
+    }
+    if key.nulls == Nulls::First {
+        try!(write!(f, " nulls first"));
+    }
+    Ok(())
+}
+
+fn write_page(f: &mut fmt::Formatter, page: &Page) -> fmt::Result {
+    if let Some(limit) = page.limit {
+        try!(write!(f, " limit {}", limit));
+    }
+    // A default page is still written, as it changes how the list is returned.
+    if page.offset > 0 || *page == Page::new() {
+        try!(write!(f, " offset {}", page.offset));
+    }
+    if let Some(ref cursor) = page.after {
+        try!(write!(f, " after "));
+        try!(write_string(f, cursor));
+    }
+    if let Some(ref cursor) = page.before {
+        try!(write!(f, " before "));
+        try!(write_string(f, cursor));
+    }
+    Ok(())
+}
+
+fn write_block(f: &mut fmt::Formatter, selector: &Selector) -> fmt::Result {
+    match selector {
+        &Selector::Multi(ref selectors) if selectors.is_empty() => write!(f, " {{ }}"),
+        selector => {
+            try!(write!(f, " {{ "));
+            try!(write_selections(f, selector));
+            write!(f, " }}")
+        },
+    }
+}
+
+/// Renders the selector as a query that `parse_query` reads back.
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Selector::Multi(ref selectors) if selectors.is_empty() => write!(f, "{{ }}"),
+            selector => {
+                try!(write!(f, "{{ "));
+                try!(write_selections(f, selector));
+                write!(f, " }}")
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expression::Expression;
+    use parser::ParseError;
+    use predicate::Predicate;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_query("{ series(year > 2010) { name episodes { name } } }").unwrap(),
+            Selector::traverse("series", Selector::Multi(vec![
+                Selector::field("name"),
+                Selector::traverse("episodes", Selector::field("name")),
+            ]).filter(Predicate::Gt("year".into(), 2010.into())))
+        );
+        assert_eq!(
+            "{ name, tags($ ^= \"c\") * }".parse::<Selector>().unwrap(),
+            Selector::Multi(vec![
+                Selector::field("name"),
+                Selector::traverse("tags", Selector::AllFields.filter(Predicate::StartsWith("$".into(), "c".into()))),
+                Selector::AllFields,
+            ])
+        );
+        assert_eq!(
+            parse_query("{ series(year > 2010) order by year desc, network.name nulls first { name } order }").unwrap(),
+            Selector::Multi(vec![
+                Selector::traverse("series", Selector::field("name")
+                    .filter(Predicate::Gt("year".into(), 2010.into()))
+                    .order_by(OrderBy::desc("year"))
+                    .order_by(OrderBy::asc("network.name").nulls(Nulls::First))),
+                Selector::field("order"),
+            ])
+        );
+        assert_eq!(
+            parse_query("{ tags order by $ desc }").unwrap(),
+            Selector::traverse("tags", Selector::AllFields.order_by(OrderBy::desc("$")))
+        );
+        assert_eq!(
+            parse_query("{ series order by name limit 2 after \"sherlock\" { name } limit }").unwrap(),
+            Selector::Multi(vec![
+                Selector::traverse("series", Selector::field("name")
+                    .order_by(OrderBy::asc("name"))
+                    .page(Page::new().limit(2).after("sherlock"))),
+                Selector::field("limit"),
+            ])
+        );
+        assert_eq!(
+            parse_query("{ series { name episodes { @count() } } tags { @distinct($) by network.name } }").unwrap(),
+            Selector::Multi(vec![
+                Selector::traverse("series", Selector::Multi(vec![
+                    Selector::field("name"),
+                    Selector::traverse("episodes", Selector::aggregate(Aggregate::Count)),
+                ])),
+                Selector::traverse("tags", Selector::aggregate_by(Aggregate::distinct("$"), "network.name")),
+            ])
+        );
+        assert_eq!(
+            parse_query("{ recent: series(year > 2010) { name } year: name title = concat(name, \"!\") }").unwrap(),
+            Selector::Multi(vec![
+                Selector::traverse("series", Selector::field("name").filter(Predicate::Gt("year".into(), 2010.into()))).alias("recent"),
+                Selector::field("name").alias("year"),
+                Selector::computed("title", Expression::Concat(vec![Expression::field("name"), Expression::constant("!")])),
+            ])
+        );
+        assert_eq!(parse_query("{}").unwrap(), Selector::Multi(vec![]));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |input| match parse_query(input) {
+            Err(Error::Parse(error)) => error,
+            result => panic!("Expected a parse error for {}, got {:?}", input, result),
+        };
+
+        assert_eq!(error("series { name }"), ParseError::new(1, "Expected `{`, found `series`"));
+        assert_eq!(error("{ series { name }"), ParseError::new(18, "Expected a field or `}`, found end of input"));
+        assert_eq!(error("{ series(year >) { name } }"), ParseError::new(16, "Expected a value, found `)`"));
+        assert_eq!(error("{ name } name"), ParseError::new(10, "Expected end of input, found `name`"));
+        assert_eq!(error("{ tags order by }"), ParseError::new(17, "Expected a field, found `}`"));
+        assert_eq!(error("{ tags order by $ nulls }"), ParseError::new(25, "Expected `first` or `last`, found `}`"));
+        assert_eq!(error("{ tags limit -1 }"), ParseError::new(14, "Expected a count, found `-1`"));
+        assert_eq!(error("{ tags { @median(a) } }"), ParseError::new(11, "Unknown aggregate `median`"));
+        assert_eq!(error("{ tags { @sum() } }"), ParseError::new(15, "Expected a field, found `)`"));
+        assert_eq!(error("{ both: { name } }"), ParseError::new(9, "Expected a field, found `{`"));
+        assert_eq!(error("{ next = year + }"), ParseError::new(17, "Expected an expression, found `}`"));
+    }
+
+    #[test]
+    fn display() {
+        let queries = [
+            "{ series(year > 2010) { name episodes { name } } }",
+            "{ name tags($ ^= \"c\") * }",
+            "{ series { * } `odd field` empty { } }",
+            "{ series(year > 2010) order by year desc, name nulls first { name } }",
+            "{ tags order by $ }",
+            "{ series(year > 2010) limit 10 offset 5 before \"a \\\"b\\\"\" { name } }",
+            "{ tags offset 0 }",
+            "{ series(year > 2010) { @avg(year) by `odd field` } tags { @count() } }",
+            "{ recent: series(year > 2010) { name } `odd alias`: name }",
+            "{ title = concat(name, \" (\", year, \")\") next = (year + 1) * 2 }",
+            "{ name }",
+            "{ }",
+        ];
+
+        for query in queries.iter() {
+            let selector = parse_query(query).unwrap();
+            assert_eq!(&selector.to_string(), query);
+        }
+    }
+}
@@ -1,47 +1,270 @@
-use entities::PrimitiveValue;
-use node::Node;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops;
+use regex::Regex;
+use entities::{Error, KakoiResult, PrimitiveValue};
+use node::{Node, NodeProperties};
+use value::Value;
 use self::Predicate::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::de::Error as DeError;
 
+/// Field name that refers to the value itself when matching scalar list elements.
+pub const ELEMENT: &'static str = "$";
+
+/// Separates the segments of a field path, like `network.name`, that follows a
+/// link or a map before comparing.
+pub const SEPARATOR: char = '.';
+
+static NULL: Value = Value::Null;
+
+/// Returns the first segment of a field path, the field of the node itself.
+pub fn root_field(field: &str) -> &str {
+    field.split(SEPARATOR).next().unwrap_or(field)
+}
+
+/// A compiled regular expression, compiled once when the predicate is created.
+#[derive(Clone, Debug)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> KakoiResult<Pattern> {
+        Regex::new(pattern).map(Pattern).map_err(Error::Regex)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn is_match(&self, string: &str) -> bool {
+        self.0.is_match(string)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+        let pattern = try!(String::deserialize(deserializer));
+        Regex::new(&pattern).map(Pattern).map_err(D::Error::custom)
+    }
+}
+
+/// A missing field is treated as null. Like in SQL, an ordering comparison with
+/// null on either side doesn't match, so null is only matched by `Eq`, `Neq`,
+/// `In`, `Exists` and `IsNull`.
 #[derive(Clone, Debug, PartialEq)]
-pub enum Predicate<'a> {
-    Eq(&'a str, PrimitiveValue),
-    Neq(&'a str, PrimitiveValue),
-    Lt(&'a str, PrimitiveValue),
-    Lte(&'a str, PrimitiveValue),
-    Gt(&'a str, PrimitiveValue),
-    Gte(&'a str, PrimitiveValue),
-    All(&'a [Predicate<'a>]),
-    Any(&'a [Predicate<'a>]),
-}
-
-impl<'a> Predicate<'a> {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Predicate {
+    Eq(String, PrimitiveValue),
+    Neq(String, PrimitiveValue),
+    Lt(String, PrimitiveValue),
+    Lte(String, PrimitiveValue),
+    Gt(String, PrimitiveValue),
+    Gte(String, PrimitiveValue),
+    All(Vec<Predicate>),
+    Any(Vec<Predicate>),
+    Exists(String),
+    IsNull(String),
+    Not(Box<Predicate>),
+    In(String, Vec<PrimitiveValue>),
+    /// Matches values between the bounds, inclusive.
+    Between(String, PrimitiveValue, PrimitiveValue),
+    StartsWith(String, String),
+    EndsWith(String, String),
+    Contains(String, String),
+    EqIgnoreCase(String, String),
+    Matches(String, Pattern),
+    /// Matches if any element of the list field matches the inner predicate.
+    AnyElement(String, Box<Predicate>),
+    /// Matches if every element of the list field matches the inner predicate.
+    AllElements(String, Box<Predicate>),
+    /// Matches if no element of the list field matches the inner predicate.
+    NoElement(String, Box<Predicate>),
+    /// Matches the number of elements in the list field, as `ELEMENT`, against the
+    /// inner predicate.
+    Count(String, Box<Predicate>),
+}
+
+impl Predicate {
+    pub fn regex<S: Into<String>>(field: S, pattern: &str) -> KakoiResult<Predicate> {
+        Pattern::new(pattern).map(|pattern| Matches(field.into(), pattern))
+    }
+
     pub fn get_fields(&self) -> Vec<&str> {
         match self {
-            &Eq(field, _) | &Neq(field, _) |
-            &Lt(field, _) | &Lte(field, _) |
-            &Gt(field, _) | &Gte(field, _) =>
+            &Eq(ref field, _) | &Neq(ref field, _) |
+            &Lt(ref field, _) | &Lte(ref field, _) |
+            &Gt(ref field, _) | &Gte(ref field, _) |
+            &Exists(ref field) | &IsNull(ref field) |
+            &In(ref field, _) | &Between(ref field, _, _) |
+            &StartsWith(ref field, _) | &EndsWith(ref field, _) |
+            &Contains(ref field, _) | &EqIgnoreCase(ref field, _) |
+            &Matches(ref field, _) |
+            &AnyElement(ref field, _) | &AllElements(ref field, _) |
+            &NoElement(ref field, _) | &Count(ref field, _) =>
                 vec![field],
-            &All(predicates) | &Any(predicates) =>
+            &Not(ref predicate) => predicate.get_fields(),
+            &All(ref predicates) | &Any(ref predicates) =>
                 predicates.iter().flat_map(Predicate::get_fields).collect(),
         }
     }
+
+    /// Matches if all the predicates match.
+    pub fn and(self, other: Predicate) -> Predicate {
+        match self {
+            All(mut predicates) => {
+                predicates.push(other);
+                All(predicates)
+            },
+            predicate => All(vec![predicate, other]),
+        }
+    }
+
+    /// Matches if any of the predicates match.
+    pub fn or(self, other: Predicate) -> Predicate {
+        match self {
+            Any(mut predicates) => {
+                predicates.push(other);
+                Any(predicates)
+            },
+            predicate => Any(vec![predicate, other]),
+        }
+    }
+}
+
+/// `!predicate` negates a predicate.
+impl ops::Not for Predicate {
+    type Output = Predicate;
+
+    fn not(self) -> Predicate {
+        Not(Box::new(self))
+    }
 }
 
 pub trait MatchesPredicate {
     fn matches(&self, predicate: &Predicate) -> bool;
 }
 
+fn get<'b>(node: &'b Node, field: &str) -> &'b Value {
+    get_property(&node.properties, field)
+}
+
+fn get_property<'b>(properties: &'b NodeProperties, field: &str) -> &'b Value {
+    if let Some(value) = properties.get(field) {
+        return value;
+    }
+
+    let mut segments = field.split(SEPARATOR);
+    let mut value = segments.next().and_then(|field| properties.get(field)).unwrap_or(&NULL);
+
+    for segment in segments {
+        value = match value {
+            &Value::Node(ref node) => node.properties.get(segment),
+            &Value::Map(ref properties) => properties.get(segment),
+            _ => None,
+        }.unwrap_or(&NULL);
+    }
+
+    value
+}
+
+/// Returns the value of a field of a node or a map, or of a value itself for
+/// `ELEMENT`, as predicates compare it.
+pub fn field_value<'b>(value: &'b Value, field: &str) -> &'b Value {
+    match value {
+        &Value::Node(ref node) => get(node, field),
+        &Value::Map(ref properties) => get_property(properties, field),
+        value if field == ELEMENT => value,
+        _ => &NULL,
+    }
+}
+
+/// A missing list is empty, other values than lists never match a quantifier.
+fn elements<'b>(node: &'b Node, field: &str) -> Option<&'b [Value]> {
+    match get(node, field) {
+        &Value::List(ref values) => Some(values),
+        &Value::Null => Some(&[]),
+        _ => None,
+    }
+}
+
+fn compare(node: &Node, field: &str, value: &PrimitiveValue, expected: &[Ordering]) -> bool {
+    match (get(node, field), value) {
+        (&Value::Null, _) | (_, &PrimitiveValue::Null) => false,
+        (field, value) => field.partial_cmp(value).map_or(false, |ordering| expected.contains(&ordering)),
+    }
+}
+
+fn matches_string<F>(node: &Node, field: &str, predicate: F) -> bool where F: Fn(&str) -> bool {
+    match get(node, field) {
+        &Value::String(ref string) => predicate(string),
+        _ => false,
+    }
+}
+
 impl MatchesPredicate for Node {
     fn matches(&self, predicate: &Predicate) -> bool {
         match predicate {
-            &All(predicates) => predicates.iter().all(|p| self.matches(p)),
-            &Any(predicates) => predicates.iter().any(|p| self.matches(p)),
-            &Eq(field, ref value) => &self.properties[field] == value,
-            &Neq(field, ref value) => &self.properties[field] != value,
-            &Lt(field, ref value) => &self.properties[field] < value,
-            &Lte(field, ref value) => &self.properties[field] <= value,
-            &Gt(field, ref value) => &self.properties[field] > value,
-            &Gte(field, ref value) => &self.properties[field] >= value,
+            &All(ref predicates) => predicates.iter().all(|p| self.matches(p)),
+            &Any(ref predicates) => predicates.iter().any(|p| self.matches(p)),
+            &Eq(ref field, ref value) => get(self, field) == value,
+            &Neq(ref field, ref value) => get(self, field) != value,
+            &Lt(ref field, ref value) => compare(self, field, value, &[Ordering::Less]),
+            &Lte(ref field, ref value) => compare(self, field, value, &[Ordering::Less, Ordering::Equal]),
+            &Gt(ref field, ref value) => compare(self, field, value, &[Ordering::Greater]),
+            &Gte(ref field, ref value) => compare(self, field, value, &[Ordering::Greater, Ordering::Equal]),
+            &Exists(ref field) => get(self, field) != &NULL,
+            &IsNull(ref field) => get(self, field) == &NULL,
+            &Not(ref predicate) => !self.matches(predicate),
+            &In(ref field, ref values) => values.iter().any(|value| get(self, field) == value),
+            &Between(ref field, ref low, ref high) =>
+                compare(self, field, low, &[Ordering::Greater, Ordering::Equal]) &&
+                    compare(self, field, high, &[Ordering::Less, Ordering::Equal]),
+            &StartsWith(ref field, ref prefix) => matches_string(self, field, |s| s.starts_with(prefix.as_str())),
+            &EndsWith(ref field, ref suffix) => matches_string(self, field, |s| s.ends_with(suffix.as_str())),
+            &Contains(ref field, ref substring) => matches_string(self, field, |s| s.contains(substring.as_str())),
+            &EqIgnoreCase(ref field, ref other) =>
+                matches_string(self, field, |s| s.to_lowercase() == other.to_lowercase()),
+            &Matches(ref field, ref pattern) => matches_string(self, field, |s| pattern.is_match(s)),
+            &AnyElement(ref field, ref predicate) =>
+                elements(self, field).map_or(false, |values| values.iter().any(|v| v.matches(predicate))),
+            &AllElements(ref field, ref predicate) =>
+                elements(self, field).map_or(false, |values| values.iter().all(|v| v.matches(predicate))),
+            &NoElement(ref field, ref predicate) =>
+                elements(self, field).map_or(false, |values| !values.iter().any(|v| v.matches(predicate))),
+            &Count(ref field, ref predicate) =>
+                elements(self, field).map_or(false, |values| Value::U64(values.len() as u64).matches(predicate)),
+        }
+    }
+}
+
+impl MatchesPredicate for Value {
+    fn matches(&self, predicate: &Predicate) -> bool {
+        match self {
+            &Value::Node(ref node) => node.matches(predicate),
+            // A map is matched on its fields, like a node.
+            &Value::Map(ref properties) => Node {id: String::new(), properties: properties.clone()}.matches(predicate),
+            _ => {
+                let mut properties = HashMap::new();
+                properties.insert(ELEMENT.to_owned(), self.clone());
+
+                Node {id: String::new(), properties: properties}.matches(predicate)
+            }
         }
     }
 }
@@ -50,6 +273,7 @@ impl MatchesPredicate for Node {
 mod tests {
     use super::*;
     use super::Predicate::*;
+    use std::cmp::Ordering;
     use std::collections::HashMap;
     use node::Node;
     use value::Value;
@@ -69,65 +293,65 @@ mod tests {
     fn eq() {
         let node = create_node();
 
-        assert!(node.matches(&Eq("string", "string".into())), "string == string");
-        assert!(!node.matches(&Eq("number", "number".into())), "!(number == number)");
+        assert!(node.matches(&Eq("string".into(), "string".into())), "string == string");
+        assert!(!node.matches(&Eq("number".into(), "number".into())), "!(number == number)");
     }
 
     #[test]
     fn neq() {
         let node = create_node();
 
-        assert!(node.matches(&Neq("string", "number".into())), "string != number");
-        assert!(!node.matches(&Neq("number", 42.into())), "!(number != 42)");
+        assert!(node.matches(&Neq("string".into(), "number".into())), "string != number");
+        assert!(!node.matches(&Neq("number".into(), 42.into())), "!(number != 42)");
     }
 
     #[test]
     fn lt() {
         let node = create_node();
 
-        assert!(node.matches(&Lt("number", 43.into())), "number < 43");
-        assert!(!node.matches(&Lt("number", 42.into())), "!(number < 42)");
+        assert!(node.matches(&Lt("number".into(), 43.into())), "number < 43");
+        assert!(!node.matches(&Lt("number".into(), 42.into())), "!(number < 42)");
     }
 
     #[test]
     fn lte() {
         let node = create_node();
 
-        assert!(node.matches(&Lte("number", 43.into())), "number <= 43");
-        assert!(node.matches(&Lte("number", 42.into())), "number <= 42");
-        assert!(!node.matches(&Lte("number", 41.into())), "!(number <= 41)");
+        assert!(node.matches(&Lte("number".into(), 43.into())), "number <= 43");
+        assert!(node.matches(&Lte("number".into(), 42.into())), "number <= 42");
+        assert!(!node.matches(&Lte("number".into(), 41.into())), "!(number <= 41)");
     }
 
     #[test]
     fn gt() {
         let node = create_node();
 
-        assert!(node.matches(&Gt("number", 41.into())), "number > 41");
-        assert!(!node.matches(&Gt("number", 42.into())), "!(number > 42)");
+        assert!(node.matches(&Gt("number".into(), 41.into())), "number > 41");
+        assert!(!node.matches(&Gt("number".into(), 42.into())), "!(number > 42)");
     }
 
     #[test]
     fn gte() {
         let node = create_node();
 
-        assert!(node.matches(&Gte("number", 41.into())), "number >= 41");
-        assert!(node.matches(&Gte("number", 42.into())), "number >= 42");
-        assert!(!node.matches(&Gte("number", 43.into())), "!(number >= 43)");
+        assert!(node.matches(&Gte("number".into(), 41.into())), "number >= 41");
+        assert!(node.matches(&Gte("number".into(), 42.into())), "number >= 42");
+        assert!(!node.matches(&Gte("number".into(), 43.into())), "!(number >= 43)");
     }
 
     #[test]
     fn all() {
         let node = create_node();
 
-        assert!(node.matches(&All(&[
-            Gt("number", 41.into()),
-            Lt("number", 43.into()),
+        assert!(node.matches(&All(vec![
+            Gt("number".into(), 41.into()),
+            Lt("number".into(), 43.into()),
         ])), "number > 41 && number < 43");
 
-        assert!(!node.matches(&All(&[
-            Gt("number", 41.into()),
-            Lt("number", 43.into()),
-            Eq("number", "number".into()),
+        assert!(!node.matches(&All(vec![
+            Gt("number".into(), 41.into()),
+            Lt("number".into(), 43.into()),
+            Eq("number".into(), "number".into()),
         ])), "!(number > 41 && number < 43 && number == number)");
     }
 
@@ -135,19 +359,211 @@ mod tests {
     fn any() {
         let node = create_node();
 
-        assert!(node.matches(&Any(&[
-            Gt("number", 41.into()),
-            Lt("number", 41.into()),
+        assert!(node.matches(&Any(vec![
+            Gt("number".into(), 41.into()),
+            Lt("number".into(), 41.into()),
         ])), "number > 41 || number < 41");
 
-        assert!(node.matches(&Any(&[
-            Gt("number", 41.into()),
-            Lt("number", 43.into()),
+        assert!(node.matches(&Any(vec![
+            Gt("number".into(), 41.into()),
+            Lt("number".into(), 43.into()),
         ])), "number > 41 || number < 43");
 
-        assert!(!node.matches(&Any(&[
-            Gt("number", 43.into()),
-            Lt("number", 41.into()),
+        assert!(!node.matches(&Any(vec![
+            Gt("number".into(), 43.into()),
+            Lt("number".into(), 41.into()),
         ])), "!(number > 43 || number < 41)");
     }
+
+    #[test]
+    fn scalar() {
+        let value = Value::I64(42);
+
+        assert!(value.matches(&Eq(ELEMENT.into(), 42.into())), "42 == 42");
+        assert!(value.matches(&All(vec![
+            Gt(ELEMENT.into(), 41.into()),
+            Lt(ELEMENT.into(), 43.into()),
+        ])), "42 > 41 && 42 < 43");
+        assert!(!Value::String("drama".into()).matches(&Eq(ELEMENT.into(), "crime".into())), "!(drama == crime)");
+    }
+
+    #[test]
+    fn mixed_numbers() {
+        let mut node = create_node();
+        node.properties.insert("negative".to_string(), Value::I64(-1));
+        node.properties.insert("big".to_string(), Value::U64(u64::max_value()));
+        node.properties.insert("float".to_string(), Value::F64(42.5));
+
+        assert!(node.matches(&Lt("number".into(), PrimitiveValue::F64(42.5))), "42 < 42.5");
+        assert!(node.matches(&Gt("number".into(), PrimitiveValue::F64(41.5))), "42 > 41.5");
+        assert!(!node.matches(&Eq("number".into(), PrimitiveValue::F64(42.5))), "!(42 == 42.5)");
+        assert!(node.matches(&Eq("number".into(), PrimitiveValue::F64(42.0))), "42 == 42.0");
+        assert!(node.matches(&Eq("number".into(), PrimitiveValue::U64(42))), "42 == 42u");
+        assert!(node.matches(&Lt("negative".into(), PrimitiveValue::U64(0))), "-1 < 0u");
+        assert!(node.matches(&Gt("big".into(), PrimitiveValue::I64(-1))), "u64::MAX > -1");
+        assert!(node.matches(&Gt("big".into(), PrimitiveValue::F64(9007199254740992.0))), "u64::MAX > 2^53");
+        assert!(node.matches(&Gt("float".into(), 42.into())), "42.5 > 42");
+        assert!(node.matches(&Lt("float".into(), PrimitiveValue::F64(::std::f64::NAN))), "42.5 < NaN");
+    }
+
+    #[test]
+    fn strings() {
+        let mut node = create_node();
+        node.properties.insert("name".to_string(), Value::String("Elementary".into()));
+
+        assert!(node.matches(&Lt("name".into(), "M".into())), "Elementary < M");
+        assert!(node.matches(&Gte("name".into(), "Elementary".into())), "Elementary >= Elementary");
+        assert!(!node.matches(&Gt("name".into(), "Sherlock".into())), "!(Elementary > Sherlock)");
+    }
+
+    #[test]
+    fn type_order() {
+        let mut node = create_node();
+        node.properties.insert("null".to_string(), Value::Null);
+        node.properties.insert("boolean".to_string(), Value::Boolean(true));
+
+        assert!(!node.matches(&Lt("null".into(), PrimitiveValue::Boolean(false))), "!(null < false)");
+        assert!(!node.matches(&Gte("null".into(), PrimitiveValue::Boolean(false))), "!(null >= false)");
+        assert!(!node.matches(&Gt("boolean".into(), PrimitiveValue::Null)), "!(true > null)");
+        assert!(node.matches(&Eq("null".into(), PrimitiveValue::Null)), "null == null");
+        assert!(node.matches(&Gt("boolean".into(), PrimitiveValue::Boolean(false))), "true > false");
+        assert!(node.matches(&Lt("boolean".into(), 0.into())), "true < 0");
+        assert!(node.matches(&Lt("number".into(), "".into())), "42 < \"\"");
+    }
+
+    #[test]
+    fn missing() {
+        let node = create_node();
+
+        assert!(!node.matches(&Eq("missing".into(), 42.into())), "!(missing == 42)");
+        assert!(node.matches(&Neq("missing".into(), 42.into())), "missing != 42");
+        assert!(node.matches(&Eq("missing".into(), PrimitiveValue::Null)), "missing == null");
+        assert!(!node.matches(&Lt("missing".into(), 42.into())), "!(missing < 42)");
+        assert!(!node.matches(&Lte("missing".into(), 42.into())), "!(missing <= 42)");
+        assert!(!node.matches(&Gte("missing".into(), 42.into())), "!(missing >= 42)");
+        assert!(!node.matches(&Lt("number".into(), PrimitiveValue::Null)), "!(number < null)");
+    }
+
+    #[test]
+    fn exists() {
+        let mut node = create_node();
+        node.properties.insert("null".to_string(), Value::Null);
+
+        assert!(node.matches(&Exists("number".into())), "exists(number)");
+        assert!(!node.matches(&Exists("null".into())), "!exists(null)");
+        assert!(!node.matches(&Exists("missing".into())), "!exists(missing)");
+    }
+
+    #[test]
+    fn is_null() {
+        let mut node = create_node();
+        node.properties.insert("null".to_string(), Value::Null);
+
+        assert!(!node.matches(&IsNull("number".into())), "!is_null(number)");
+        assert!(node.matches(&IsNull("null".into())), "is_null(null)");
+        assert!(node.matches(&IsNull("missing".into())), "is_null(missing)");
+    }
+
+    #[test]
+    fn not() {
+        let node = create_node();
+
+        assert!(node.matches(&Not(Box::new(Eq("number".into(), 43.into())))), "!(number == 43)");
+        assert!(!node.matches(&Not(Box::new(Any(vec![
+            Eq("number".into(), 42.into()),
+            Eq("number".into(), 43.into()),
+        ])))), "!!(number == 42 || number == 43)");
+    }
+
+    #[test]
+    fn in_values() {
+        let node = create_node();
+
+        assert!(node.matches(&In("number".into(), vec![41.into(), 42.into()])), "number in [41, 42]");
+        assert!(!node.matches(&In("number".into(), vec![2010.into(), 2012.into()])), "!(number in [2010, 2012])");
+        assert!(node.matches(&Not(Box::new(In("number".into(), vec![2010.into(), 2012.into()])))), "number not in [2010, 2012]");
+        assert!(!node.matches(&In("number".into(), vec![])), "!(number in [])");
+    }
+
+    #[test]
+    fn between() {
+        let node = create_node();
+
+        assert!(node.matches(&Between("number".into(), 41.into(), 43.into())), "41 <= number <= 43");
+        assert!(node.matches(&Between("number".into(), 42.into(), 42.into())), "42 <= number <= 42");
+        assert!(!node.matches(&Between("number".into(), 43.into(), 50.into())), "!(43 <= number <= 50)");
+        assert!(!node.matches(&Between("missing".into(), 41.into(), 43.into())), "!(41 <= missing <= 43)");
+    }
+
+    #[test]
+    fn get_fields() {
+        assert_eq!(All(vec![
+            Not(Box::new(Eq("a".into(), 1.into()))),
+            In("b".into(), vec![]),
+            Between("c".into(), 1.into(), 2.into()),
+        ]).get_fields(), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn strings_matching() {
+        let mut node = create_node();
+        node.properties.insert("name".to_string(), Value::String("Elementary".into()));
+
+        assert!(node.matches(&StartsWith("name".into(), "Ele".into())), "name ^= Ele");
+        assert!(!node.matches(&StartsWith("name".into(), "ele".into())), "!(name ^= ele)");
+        assert!(node.matches(&EndsWith("name".into(), "tary".into())), "name $= tary");
+        assert!(node.matches(&Contains("name".into(), "men".into())), "name *= men");
+        assert!(!node.matches(&Contains("number".into(), "4".into())), "!(number *= 4)");
+        assert!(node.matches(&EqIgnoreCase("name".into(), "ELEMENTARY".into())), "name ~= ELEMENTARY");
+        assert!(!node.matches(&EqIgnoreCase("name".into(), "ELEMENT".into())), "!(name ~= ELEMENT)");
+        assert!(!node.matches(&StartsWith("missing".into(), "".into())), "!(missing ^= '')");
+    }
+
+    #[test]
+    fn regex() {
+        let mut node = create_node();
+        node.properties.insert("name".to_string(), Value::String("Elementary".into()));
+
+        assert!(node.matches(&Predicate::regex("name", "^E.*y$").unwrap()), "name =~ /^E.*y$/");
+        assert!(node.matches(&Predicate::regex("name", "(?i)^elem").unwrap()), "name =~ /(?i)^elem/");
+        assert!(!node.matches(&Predicate::regex("name", "^S").unwrap()), "!(name =~ /^S/)");
+        assert!(matches!(Predicate::regex("name", "("), Err(Error::Regex(_))));
+        assert_eq!(Predicate::regex("name", "^E").unwrap(), Predicate::regex("name", "^E").unwrap());
+    }
+
+    #[test]
+    fn nested() {
+        let mut node = create_node();
+        let mut address = HashMap::new();
+        address.insert("city".to_string(), Value::String("London".into()));
+        node.properties.insert("network".to_string(), Value::Node(Node {
+            id: "bbc".to_string(),
+            properties: vec![
+                ("name".to_string(), Value::String("BBC".into())),
+                ("address".to_string(), Value::Map(address)),
+            ].into_iter().collect(),
+        }));
+
+        assert!(node.matches(&Eq("network.name".into(), "BBC".into())), "network.name == BBC");
+        assert!(node.matches(&Eq("network.address.city".into(), "London".into())), "network.address.city == London");
+        assert!(!node.matches(&Exists("network.missing.name".into())), "!exists(network.missing.name)");
+        assert!(!node.matches(&Exists("number.name".into())), "!exists(number.name)");
+        assert_eq!(root_field("network.address.city"), "network");
+        assert_eq!(root_field("number"), "number");
+    }
+
+    #[test]
+    fn quantifiers() {
+        let mut node = create_node();
+        node.properties.insert("ratings".to_string(), Value::List(vec![Value::I64(8), Value::I64(9)]));
+
+        assert!(node.matches(&AnyElement("ratings".into(), Box::new(Eq(ELEMENT.into(), 9.into())))), "any(ratings == 9)");
+        assert!(node.matches(&AllElements("ratings".into(), Box::new(Gt(ELEMENT.into(), 7.into())))), "all(ratings > 7)");
+        assert!(node.matches(&NoElement("ratings".into(), Box::new(Lt(ELEMENT.into(), 5.into())))), "none(ratings < 5)");
+        assert!(node.matches(&Count("ratings".into(), Box::new(Eq(ELEMENT.into(), 2.into())))), "count(ratings) == 2");
+        assert!(!node.matches(&AnyElement("missing".into(), Box::new(Eq(ELEMENT.into(), 9.into())))), "!any(missing == 9)");
+        assert!(node.matches(&AllElements("missing".into(), Box::new(Eq(ELEMENT.into(), 9.into())))), "all(missing == 9)");
+        assert!(node.matches(&Count("missing".into(), Box::new(Eq(ELEMENT.into(), 0.into())))), "count(missing) == 0");
+        assert!(!node.matches(&NoElement("number".into(), Box::new(Eq(ELEMENT.into(), 9.into())))), "!none(number == 9)");
+    }
 }
@@ -1,13 +1,17 @@
 use std::cmp::{Ordering, PartialEq, PartialOrd};
 use std::collections::HashMap;
-use std::convert::From;
-use uuid::Uuid;
+use std::convert::{From, TryFrom};
+use encoding;
 use entities::{Error, Path, PathPart, PrimitiveValue};
-use node::Node;
+use id::IdGenerator;
+use node::{Node, NodeProperties};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 pub type KakoiResult<T = ()> = Result<T, Error>;
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Value {
     I64(i64),
     U64(u64),
@@ -16,8 +20,9 @@ pub enum Value {
     String(String),
     Node(Node),
     Link(String),
-    List(Vec<Node>),
+    List(Vec<Value>),
     ListLink(String),
+    Map(NodeProperties),
     Error(String),
     Null,
 }
@@ -27,14 +32,35 @@ pub struct List {
     pub values: Vec<Value>,
 }
 
-pub struct ValueResolver {
+/// Flattens nested nodes and lists into links, collecting the nodes and lists that
+/// have to be stored. `stored` tells whether a `Link` or `ListLink` already exists.
+pub struct ValueResolver<'a> {
     pub lists: Vec<List>,
     pub nodes: Vec<Node>,
+    ids: &'a mut IdGenerator,
+    stored: &'a Fn(&Value) -> bool,
 }
 
-impl ValueResolver {
-    pub fn new() -> ValueResolver {
-        ValueResolver {lists: Vec::new(), nodes: Vec::new()}
+impl<'a> ValueResolver<'a> {
+    pub fn new(ids: &'a mut IdGenerator, stored: &'a Fn(&Value) -> bool) -> ValueResolver<'a> {
+        ValueResolver {lists: Vec::new(), nodes: Vec::new(), ids: ids, stored: stored}
+    }
+
+    /// Generates ids until one is unused, or returns a content addressed id along with
+    /// whether it still has to be stored.
+    fn generate<F: FnMut(&mut IdGenerator) -> String>(&mut self, link: fn(String) -> Value, mut generate: F) -> (String, bool) {
+        loop {
+            let id = generate(&mut *self.ids);
+            let stored = (self.stored)(&link(id.clone()));
+
+            if self.ids.content_addressed() {
+                let pending = self.lists.iter().any(|list| list.id == id);
+                return (id, !stored && !pending);
+            }
+            if !stored {
+                return (id, true);
+            }
+        }
     }
 
     fn resolve_node(&mut self, node: &mut Node, path: Path) -> Value {
@@ -44,33 +70,51 @@ impl ValueResolver {
             let flattened_value;
             {
                 let mut sub_path = path.to_vec();
-                sub_path.push(PathPart::Field(&property));
+                sub_path.push(PathPart::Field(property.clone()));
                 flattened_value = self.resolve(value, &sub_path);
             }
 
             properties.insert(property, flattened_value);
         }
 
-        self.nodes.push(Node {id: node.id.clone(), properties: properties});
+        if !node.id.is_empty() {
+            self.nodes.push(Node {id: node.id.clone(), properties: properties});
+            return Value::Link(node.id.clone());
+        }
 
-        Value::Link(node.id.clone())
+        let (id, store) = self.generate(Value::Link, |ids| ids.node_id(&properties));
+        if store {
+            self.nodes.push(Node {id: id.clone(), properties: properties});
+        }
+
+        Value::Link(id)
     }
 
     pub fn resolve(&mut self, value: Value, path: Path) -> Value {
         match value {
             Value::Node(mut node) => self.resolve_node(&mut node, path),
-            Value::List(mut nodes) => {
-                let id = Uuid::new_v4().simple().to_string();
-
-                let list = List {
-                    id: id.clone(),
-                    values: nodes.iter_mut().map(|mut n| self.resolve_node(&mut n, path)).collect(),
-                };
-
-                self.lists.push(list);
+            Value::List(values) => {
+                let values: Vec<Value> = values.into_iter().map(|v| self.resolve(v, path)).collect();
+                let (id, store) = self.generate(Value::ListLink, |ids| ids.list_id(&values));
+                if store {
+                    self.lists.push(List {id: id.clone(), values: values});
+                }
 
                 Value::ListLink(id)
             },
+            Value::Map(properties) => {
+                let properties = properties
+                    .into_iter()
+                    .map(|(property, value)| {
+                        let mut sub_path = path.to_vec();
+                        sub_path.push(PathPart::Field(property.clone()));
+                        let value = self.resolve(value, &sub_path);
+                        (property, value)
+                    })
+                    .collect();
+
+                Value::Map(properties)
+            },
             _ => value,
         }
     }
@@ -91,13 +135,7 @@ impl From<PrimitiveValue> for Value {
 
 impl<'a> From<&'a str> for Value {
     fn from(string: &str) -> Self {
-        match string.chars().next() {
-            Some(prefixes::LINK) => Value::Link(string[1..].to_string()),
-            Some(prefixes::LIST) => Value::ListLink(string[1..].to_string()),
-            Some(prefixes::STRING) => Value::String(string[1..].to_string()),
-            Some(c) => Value::Error(format!("Invalid initial character {}", c)),
-            None => Value::Null,
-        }
+        encoding::decode(string)
     }
 }
 
@@ -108,98 +146,220 @@ impl From<String> for Value {
     }
 }
 
-impl From<Value> for String {
-    fn from(value: Value) -> Self {
-        match value {
-            Value::Link(ref node_id) => format!("{}{}", prefixes::LINK, node_id),
-            Value::ListLink(ref id) => format!("{}{}", prefixes::LIST, id),
-            Value::String(ref string) => format!("{}{}", prefixes::STRING, string),
-            _ => panic!("Can't encode value {:?} as a string", value)
-        }
+impl TryFrom<Value> for String {
+    type Error = Error;
+
+    fn try_from(value: Value) -> KakoiResult<Self> {
+        encoding::encode(&value)
     }
 }
 
-impl From<Value> for PrimitiveValue {
-    fn from(value: Value) -> Self {
-        match value {
+impl TryFrom<Value> for PrimitiveValue {
+    type Error = Error;
+
+    fn try_from(value: Value) -> KakoiResult<Self> {
+        Ok(match value {
             Value::I64(num) => PrimitiveValue::I64(num),
             Value::U64(num) => PrimitiveValue::U64(num),
             Value::F64(num) => PrimitiveValue::F64(num),
             Value::Boolean(boolean) => PrimitiveValue::Boolean(boolean),
             Value::Null => PrimitiveValue::Null,
-            Value::Link(_) | Value::ListLink(_) | Value::String(_) =>
-                PrimitiveValue::String(value.into()),
-            _ => panic!("Value {:?} can't be transformed to a primitive value", value),
-        }
+            Value::Link(_) | Value::ListLink(_) | Value::String(_) | Value::Map(_) =>
+                PrimitiveValue::String(try!(String::try_from(value))),
+            _ => return Err(Error::InvalidValue(
+                format!("Value {:?} can't be transformed to a primitive value", value))),
+        })
     }
 }
 
-impl PartialEq<PrimitiveValue> for Value {
-    fn eq(&self, other: &PrimitiveValue) -> bool {
-        match self {
-            &Value::I64(ref value) => match other {
-                &PrimitiveValue::I64(ref other) => value == other,
-                &PrimitiveValue::U64(ref other) => *value == *other as i64,
-                &PrimitiveValue::F64(ref other) => *value == *other as i64,
-                _ => false,
-            },
-            &Value::U64(ref value) => match other {
-                &PrimitiveValue::I64(ref other) => *value == *other as u64,
-                &PrimitiveValue::U64(ref other) => value == other,
-                &PrimitiveValue::F64(ref other) => *value == *other as u64,
-                _ => false,
-            },
-            &Value::F64(ref value) => match other {
-                &PrimitiveValue::I64(ref other) => *value == *other as f64,
-                &PrimitiveValue::U64(ref other) => *value == *other as f64,
-                &PrimitiveValue::F64(ref other) => value == other,
-                _ => false,
+impl Value {
+    /// Compares values of any type. Values are first ordered by type: null, booleans,
+    /// numbers, strings, links, list links, maps, nodes, lists and errors. Numbers
+    /// are compared exactly across `I64`, `U64` and `F64`, with NaN after all other
+    /// numbers, and strings are compared lexicographically.
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (&Value::Boolean(ref a), &Value::Boolean(ref b)) => a.cmp(b),
+            (&Value::String(ref a), &Value::String(ref b)) |
+            (&Value::Link(ref a), &Value::Link(ref b)) |
+            (&Value::ListLink(ref a), &Value::ListLink(ref b)) |
+            (&Value::Error(ref a), &Value::Error(ref b)) => a.cmp(b),
+            (&Value::Map(ref a), &Value::Map(ref b)) => compare_properties(a, b),
+            (&Value::Node(ref a), &Value::Node(ref b)) =>
+                a.id.cmp(&b.id).then_with(|| compare_properties(&a.properties, &b.properties)),
+            (&Value::List(ref a), &Value::List(ref b)) => a
+                .iter()
+                .zip(b.iter())
+                .map(|(a, b)| a.total_cmp(b))
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            _ => match (Number::from_value(self), Number::from_value(other)) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => self.type_rank().cmp(&other.type_rank()),
             },
-            &Value::Boolean(ref value) => match other {
-                &PrimitiveValue::Boolean(ref other) => value == other,
-                _ => false,
-            },
-            &Value::String(ref value) => match other {
-                &PrimitiveValue::String(ref other) => value == other,
-                _ => false,
-            },
-            &Value::Null => match other {
-                &PrimitiveValue::Null => true,
-                _ => false,
+        }
+    }
+
+    fn type_rank(&self) -> u8 {
+        match self {
+            &Value::Null => 0,
+            &Value::Boolean(_) => 1,
+            &Value::I64(_) | &Value::U64(_) | &Value::F64(_) => 2,
+            &Value::String(_) => 3,
+            &Value::Link(_) => 4,
+            &Value::ListLink(_) => 5,
+            &Value::Map(_) => 6,
+            &Value::Node(_) => 7,
+            &Value::List(_) => 8,
+            &Value::Error(_) => 9,
+        }
+    }
+}
+
+fn compare_properties(a: &NodeProperties, b: &NodeProperties) -> Ordering {
+    fn sorted(properties: &NodeProperties) -> Vec<(&String, &Value)> {
+        let mut entries: Vec<(&String, &Value)> = properties.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+
+    let (a, b) = (sorted(a), sorted(b));
+
+    a
+        .iter()
+        .zip(b.iter())
+        .map(|(a, b)| a.0.cmp(b.0).then_with(|| a.1.total_cmp(b.1)))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// Integers of both signs fit in an `i128` so they can be compared with each other
+/// without overflow, and with floats without losing precision.
+enum Number {
+    Integer(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn from_value(value: &Value) -> Option<Number> {
+        match value {
+            &Value::I64(num) => Some(Number::Integer(num as i128)),
+            &Value::U64(num) => Some(Number::Integer(num as i128)),
+            &Value::F64(num) => Some(Number::Float(num)),
+            _ => None,
+        }
+    }
+
+    fn cmp(&self, other: &Number) -> Ordering {
+        match (self, other) {
+            (&Number::Integer(a), &Number::Integer(b)) => a.cmp(&b),
+            (&Number::Float(a), &Number::Float(b)) => match (a.is_nan(), b.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => a.partial_cmp(&b).unwrap(),
             },
-            _ => false,
+            (&Number::Integer(a), &Number::Float(b)) => compare_integer_float(a, b),
+            (&Number::Float(a), &Number::Integer(b)) => compare_integer_float(b, a).reverse(),
         }
     }
 }
 
+fn compare_integer_float(integer: i128, float: f64) -> Ordering {
+    if float.is_nan() {
+        return Ordering::Less;
+    }
+
+    // Rounding is monotonic so if the rounded integer differs from the float, so
+    // does the exact integer. If they are equal the float is integral and small
+    // enough to be compared as an integer.
+    match (integer as f64).partial_cmp(&float).unwrap() {
+        Ordering::Equal => integer.cmp(&(float as i128)),
+        ordering => ordering,
+    }
+}
+
+fn primitive_as_value(value: &PrimitiveValue) -> Value {
+    match value {
+        &PrimitiveValue::I64(num) => Value::I64(num),
+        &PrimitiveValue::U64(num) => Value::U64(num),
+        &PrimitiveValue::F64(num) => Value::F64(num),
+        &PrimitiveValue::Boolean(boolean) => Value::Boolean(boolean),
+        &PrimitiveValue::String(ref string) => Value::String(string.clone()),
+        &PrimitiveValue::Null => Value::Null,
+    }
+}
+
+impl PartialEq<PrimitiveValue> for Value {
+    fn eq(&self, other: &PrimitiveValue) -> bool {
+        self.total_cmp(&primitive_as_value(other)) == Ordering::Equal
+    }
+}
+
 impl PartialOrd<PrimitiveValue> for Value {
     fn partial_cmp(&self, other: &PrimitiveValue) -> Option<Ordering> {
-        match self {
-            &Value::I64(ref num) => match other {
-                &PrimitiveValue::I64(ref other) => num.partial_cmp(other),
-                &PrimitiveValue::U64(ref other) => num.partial_cmp(&(*other as i64)),
-                &PrimitiveValue::F64(ref other) => num.partial_cmp(&(*other as i64)),
-                _ => None,
-            },
-            &Value::U64(ref num) => match other {
-                &PrimitiveValue::I64(ref other) => num.partial_cmp(&(*other as u64)),
-                &PrimitiveValue::U64(ref other) => num.partial_cmp(other),
-                &PrimitiveValue::F64(ref other) => num.partial_cmp(&(*other as u64)),
-                _ => None,
-            },
-            &Value::F64(ref num) => match other {
-                &PrimitiveValue::I64(ref other) => num.partial_cmp(&(*other as f64)),
-                &PrimitiveValue::U64(ref other) => num.partial_cmp(&(*other as f64)),
-                &PrimitiveValue::F64(ref other) => num.partial_cmp(other),
-                _ => None,
-            },
-            _ => None,
-        }
+        Some(self.total_cmp(&primitive_as_value(other)))
     }
 }
 
-mod prefixes {
-    pub const LINK: char = 'L';
-    pub const STRING: char = 'S';
-    pub const LIST: char = 'l';
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use std::collections::HashMap;
+    use serde_json;
+    use entities::{Mutation, MutationOperation, PrimitiveValue, Selector};
+    use node::Node;
+    use parser::parse_path;
+    use predicate::Predicate;
+    use super::Value;
+
+    #[test]
+    fn serde_round_trip() {
+        let mut node = Node {id: "sherlock".to_string(), properties: HashMap::new()};
+        node.properties.insert("name".to_string(), Value::String("Sherlock".into()));
+        node.properties.insert("year".to_string(), Value::I64(2010));
+        node.properties.insert("tags".to_string(), Value::List(vec![Value::Boolean(true), Value::Null]));
+
+        let value = Value::Node(node);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+
+        let primitive = PrimitiveValue::U64(42);
+        let json = serde_json::to_string(&primitive).unwrap();
+        assert_eq!(json, r#"{"U64":42}"#);
+        assert_eq!(serde_json::from_str::<PrimitiveValue>(&json).unwrap(), primitive);
+    }
+
+    #[test]
+    fn serialize_selector() {
+        let selector = Selector::traverse("series", Selector::field("name").filter(Predicate::Gt("year".into(), 2010.into())));
+        let json = serde_json::to_string(&selector).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"Traverse":["series",{"selector":{"Field":"name"},"filter":{"Gt":["year",{"I64":2010}]}}]}"#
+        );
+        assert_eq!(serde_json::from_str::<Selector>(&json).unwrap(), selector);
+
+        let predicate = !Predicate::regex("name", "^S").unwrap();
+        let json = serde_json::to_string(&predicate).unwrap();
+
+        assert_eq!(json, r#"{"Not":{"Matches":["name","^S"]}}"#);
+        assert_eq!(serde_json::from_str::<Predicate>(&json).unwrap(), predicate);
+        assert!(serde_json::from_str::<Predicate>(r#"{"Matches":["name","("]}"#).is_err());
+
+        let selector: Selector = "{ recent: series(year > 2010) order by name limit 2 { name next = year + 1 } }".parse().unwrap();
+        let json = serde_json::to_string(&selector).unwrap();
+        assert_eq!(serde_json::from_str::<Selector>(&json).unwrap(), selector);
+    }
+
+    #[test]
+    fn serialize_mutation() {
+        let mutation = Mutation {
+            path: parse_path("series[name == \"Sherlock\"].year").unwrap(),
+            opertaion: MutationOperation::Set(Value::I64(2010)),
+        };
+        let json = serde_json::to_string(&mutation).unwrap();
+
+        assert_eq!(serde_json::from_str::<Mutation>(&json).unwrap(), mutation);
+    }
 }